@@ -0,0 +1,149 @@
+use crate::common::traits::aux_store::AuxStore;
+use crate::common::traits::header_backend::HeaderBackend;
+use crate::common::traits::verifier::Verifier;
+use crate::common::types::aura_authority_set::AuraAuthoritySet;
+use crate::common::types::block_import_params::BlockImportParams;
+use crate::common::types::block_origin::BlockOrigin;
+use crate::common::utils::{fetch_aura_authority_set, AURA_AUTHORITIES_CHANGE_INTERMEDIATE_KEY};
+use parity_scale_codec::alloc::borrow::Cow;
+use parity_scale_codec::alloc::sync::Arc;
+use parity_scale_codec::{Decode, Encode};
+use sp_runtime::generic::{BlockId, OpaqueDigestItemId};
+use sp_runtime::traits::{Block as BlockT, Header};
+use sp_runtime::ConsensusEngineId;
+
+/// The `ConsensusEngineId` of Aura.
+pub const AURA_ENGINE_ID: ConsensusEngineId = *b"aura";
+
+/// Mirrors `ConsensusLog` of `sp-consensus-aura`. Public keys of both
+/// signature schemes are 32 bytes long, so they are decoded as such.
+#[derive(Clone, Encode, Decode)]
+pub(crate) enum ConsensusLog {
+    #[codec(index = "1")]
+    AuthoritiesChange(Vec<[u8; 32]>),
+    #[codec(index = "2")]
+    OnDisabled(u32),
+}
+
+/// Gives slot number claimed by the header author.
+pub(crate) fn find_pre_digest<B: BlockT>(header: &B::Header) -> Option<u64> {
+    let id = OpaqueDigestItemId::PreRuntime(&AURA_ENGINE_ID);
+
+    header.digest().convert_first(|l| l.try_to(id))
+}
+
+pub(crate) fn find_authorities_change<B: BlockT>(header: &B::Header) -> Option<Vec<[u8; 32]>> {
+    let id = OpaqueDigestItemId::Consensus(&AURA_ENGINE_ID);
+
+    let filter_log = |log: ConsensusLog| match log {
+        ConsensusLog::AuthoritiesChange(authorities) => Some(authorities),
+        _ => None,
+    };
+
+    header
+        .digest()
+        .convert_first(|l| l.try_to(id).and_then(filter_log))
+}
+
+/// Checks that header is sealed by the authority its slot is assigned to.
+pub(crate) fn check_header<B: BlockT>(
+    mut header: B::Header,
+    slot_number: u64,
+    authority_set: &AuraAuthoritySet,
+) -> Result<(), String> {
+    let seal = header
+        .digest_mut()
+        .pop()
+        .ok_or_else(|| "Header is unsealed".to_string())?;
+    let signature: [u8; 64] = seal
+        .try_to(OpaqueDigestItemId::Seal(&AURA_ENGINE_ID))
+        .ok_or_else(|| "Header has bad Aura seal".to_string())?;
+    let author = authority_set
+        .slot_author(slot_number)
+        .ok_or_else(|| "Aura authority set is empty".to_string())?;
+
+    // Seal signs hash of the header without the seal itself
+    if !authority_set.verify(&signature, header.hash().as_ref(), author) {
+        return Err("Bad signature on Aura seal".into());
+    }
+
+    Ok(())
+}
+
+/// Verifies Aura seals of headers, which are not justified, and tracks authority set
+/// changes they signal. Headers are passed to the inner verifier afterwards. Nothing
+/// is verified unless Aura authority set is stored, in which case inner verifier is used alone.
+pub struct AuraVerifier<S, V> {
+    storage: Arc<S>,
+    inner: V,
+}
+
+impl<S, V> AuraVerifier<S, V> {
+    pub fn new(storage: Arc<S>, inner: V) -> Self {
+        Self { storage, inner }
+    }
+}
+
+impl<S, V, Block> Verifier<Block> for AuraVerifier<S, V>
+where
+    S: AuxStore + HeaderBackend<Block>,
+    V: Verifier<Block>,
+    Block: BlockT,
+{
+    fn verify(
+        &mut self,
+        origin: BlockOrigin,
+        header: <Block as BlockT>::Header,
+        justification: Option<Vec<u8>>,
+        body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+    ) -> Result<BlockImportParams<Block>, String> {
+        let authority_set =
+            match fetch_aura_authority_set(self.storage.clone()).map_err(|e| format!("{}", e))? {
+                Some(authority_set) => authority_set,
+                None => return self.inner.verify(origin, header, justification, body),
+            };
+
+        let slot_number = find_pre_digest::<Block>(&header)
+            .ok_or_else(|| "Header has no Aura pre-runtime digest".to_string())?;
+
+        // Parent is unknown if header skips ahead, and has no pre-runtime
+        // digest if it is the initial header of a chain without Aura.
+        let possible_parent_slot_number = self
+            .storage
+            .header(BlockId::<Block>::Hash(*header.parent_hash()))
+            .map_err(|e| format!("{}", e))?
+            .and_then(|parent_header| find_pre_digest::<Block>(&parent_header));
+        if let Some(parent_slot_number) = possible_parent_slot_number {
+            if slot_number <= parent_slot_number {
+                return Err(format!(
+                    "Slot {} of header does not follow slot {} of its parent",
+                    slot_number, parent_slot_number
+                ));
+            }
+        }
+
+        // Justified headers are verified by GRANDPA, and only update authority set
+        if justification.is_none() {
+            check_header::<Block>(header.clone(), slot_number, &authority_set)?;
+        }
+
+        // New authorities take turns starting with the header following the one signalling them
+        let possible_next_authority_set = match find_authorities_change::<Block>(&header) {
+            Some(authorities) if authorities.is_empty() => {
+                return Err("Aura authority set change need to have at least one authority".into())
+            }
+            Some(authorities) => Some(AuraAuthoritySet::new(authority_set.crypto, authorities)),
+            None => None,
+        };
+
+        let mut block_import_params = self.inner.verify(origin, header, justification, body)?;
+        if let Some(next_authority_set) = possible_next_authority_set {
+            block_import_params.intermediates.insert(
+                Cow::from(AURA_AUTHORITIES_CHANGE_INTERMEDIATE_KEY),
+                Box::new(next_authority_set),
+            );
+        }
+
+        Ok(block_import_params)
+    }
+}
@@ -0,0 +1,532 @@
+use crate::common::traits::aux_store::AuxStore;
+use crate::common::traits::header_backend::HeaderBackend;
+use crate::common::traits::verifier::Verifier;
+use crate::common::types::babe_epoch::{BabeAuthorityList, BabeEpoch, BabeEpochChanges};
+use crate::common::types::block_import_params::BlockImportParams;
+use crate::common::types::block_origin::BlockOrigin;
+use crate::common::utils::{
+    fetch_babe_epoch, fetch_next_babe_epoch, BABE_EPOCH_CHANGES_INTERMEDIATE_KEY,
+};
+use merlin::Transcript;
+use parity_scale_codec::alloc::borrow::Cow;
+use parity_scale_codec::alloc::sync::Arc;
+use parity_scale_codec::{Decode, Encode};
+use schnorrkel::vrf::{VRFOutput, VRFProof};
+use sp_core::sr25519::{Pair, Public, Signature};
+use sp_core::{blake2_256, Pair as PairT, U256};
+use sp_runtime::generic::{BlockId, OpaqueDigestItemId};
+use sp_runtime::traits::{Block as BlockT, Header};
+use sp_runtime::ConsensusEngineId;
+
+/// The `ConsensusEngineId` of BABE.
+pub const BABE_ENGINE_ID: ConsensusEngineId = *b"BABE";
+
+/// VRF output is turned into a number compared against threshold using this context.
+const BABE_VRF_PREFIX: &[u8] = b"substrate-babe-vrf";
+
+// Fixed point numbers used to calculate threshold have this many fractional bits,
+// so that product of two numbers lower than two still fits into `u128`.
+const FRACTIONAL_BITS: u32 = 63;
+const ONE: u128 = 1 << FRACTIONAL_BITS;
+
+// Types below mirror the ones of `sp-consensus-babe`, so that
+// digests produced by BABE authorities decode the same way.
+
+/// Slot claim of BABE block author, carried by `PreRuntime` digest.
+#[derive(Clone, Encode, Decode)]
+pub(crate) enum PreDigest {
+    #[codec(index = "1")]
+    Primary(PrimaryPreDigest),
+    #[codec(index = "2")]
+    Secondary(SecondaryPreDigest),
+}
+
+impl PreDigest {
+    pub(crate) fn slot_number(&self) -> u64 {
+        match self {
+            PreDigest::Primary(primary) => primary.slot_number,
+            PreDigest::Secondary(secondary) => secondary.slot_number,
+        }
+    }
+}
+
+/// Claim of a slot won by VRF output being below threshold.
+#[derive(Clone, Encode, Decode)]
+pub(crate) struct PrimaryPreDigest {
+    pub authority_index: u32,
+    pub slot_number: u64,
+    pub vrf_output: [u8; 32],
+    pub vrf_proof: [u8; 64],
+}
+
+/// Claim of a slot assigned to the authority when no primary claim is made.
+#[derive(Clone, Encode, Decode)]
+pub(crate) struct SecondaryPreDigest {
+    pub authority_index: u32,
+    pub slot_number: u64,
+}
+
+/// Epoch announced by the first header of the epoch preceding it.
+#[derive(Clone, Encode, Decode)]
+pub(crate) struct NextEpochDescriptor {
+    pub authorities: BabeAuthorityList,
+    pub randomness: [u8; 32],
+}
+
+#[derive(Clone, Encode, Decode)]
+pub(crate) enum AllowedSlots {
+    PrimarySlots,
+    PrimaryAndSecondaryPlainSlots,
+}
+
+/// Configuration change taking effect along with the announced epoch.
+#[derive(Clone, Encode, Decode)]
+pub(crate) enum NextConfigDescriptor {
+    #[codec(index = "1")]
+    V1 {
+        c: (u64, u64),
+        allowed_slots: AllowedSlots,
+    },
+}
+
+#[derive(Clone, Encode, Decode)]
+pub(crate) enum ConsensusLog {
+    #[codec(index = "1")]
+    NextEpochData(NextEpochDescriptor),
+    #[codec(index = "2")]
+    OnDisabled(u32),
+    #[codec(index = "3")]
+    NextConfigData(NextConfigDescriptor),
+}
+
+pub(crate) fn find_pre_digest<B: BlockT>(header: &B::Header) -> Option<PreDigest> {
+    let id = OpaqueDigestItemId::PreRuntime(&BABE_ENGINE_ID);
+
+    header.digest().convert_first(|l| l.try_to(id))
+}
+
+pub(crate) fn find_next_epoch_descriptor<B: BlockT>(
+    header: &B::Header,
+) -> Option<NextEpochDescriptor> {
+    let id = OpaqueDigestItemId::Consensus(&BABE_ENGINE_ID);
+
+    let filter_log = |log: ConsensusLog| match log {
+        ConsensusLog::NextEpochData(descriptor) => Some(descriptor),
+        _ => None,
+    };
+
+    header
+        .digest()
+        .convert_first(|l| l.try_to(id).and_then(filter_log))
+}
+
+pub(crate) fn find_next_config_descriptor<B: BlockT>(
+    header: &B::Header,
+) -> Option<NextConfigDescriptor> {
+    let id = OpaqueDigestItemId::Consensus(&BABE_ENGINE_ID);
+
+    let filter_log = |log: ConsensusLog| match log {
+        ConsensusLog::NextConfigData(descriptor) => Some(descriptor),
+        _ => None,
+    };
+
+    header
+        .digest()
+        .convert_first(|l| l.try_to(id).and_then(filter_log))
+}
+
+/// Transcript, which VRF output of primary slot claim is signed over.
+pub(crate) fn make_transcript(randomness: &[u8], slot_number: u64, epoch_index: u64) -> Transcript {
+    let mut transcript = Transcript::new(&BABE_ENGINE_ID);
+    transcript.append_u64(b"slot number", slot_number);
+    transcript.append_u64(b"current epoch", epoch_index);
+    transcript.append_message(b"chain randomness", randomness);
+    transcript
+}
+
+/// Gives authority, which secondary slot is assigned to.
+pub(crate) fn secondary_slot_author(
+    slot_number: u64,
+    authorities: &BabeAuthorityList,
+    randomness: [u8; 32],
+) -> Option<&Public> {
+    if authorities.is_empty() {
+        return None;
+    }
+
+    let rand = U256::from((randomness, slot_number).using_encoded(blake2_256));
+    let authorities_len = U256::from(authorities.len());
+    let idx = rand % authorities_len;
+
+    authorities
+        .get(idx.as_u32() as usize)
+        .map(|(authority, _)| authority)
+}
+
+/// Calculates threshold `2^128 * (1 - (1 - c)^theta)`, which VRF output of primary
+/// slot claim need to be below, where `theta` is relative weight of the authority.
+/// Upstream uses floating point arithmetic, which contracts can not use, so it is
+/// approximated with fixed point arithmetic instead.
+pub(crate) fn calculate_primary_threshold(
+    c: (u64, u64),
+    authorities: &BabeAuthorityList,
+    authority_index: usize,
+) -> u128 {
+    let weight = authorities
+        .get(authority_index)
+        .map(|(_, weight)| *weight as u128)
+        .unwrap_or(0);
+    let total_weight: u128 = authorities.iter().map(|(_, weight)| *weight as u128).sum();
+    if c.0 == 0 || c.1 == 0 || weight == 0 {
+        return 0;
+    }
+    if c.0 >= c.1 {
+        return u128::max_value();
+    }
+
+    let log = log2_fraction(c.1 as u128, (c.1 - c.0) as u128);
+    // theta * log2(1 / (1 - c)) is at most log2(1 / (1 - c)), so it fits into `u128`
+    let exponent = (U256::from(log) * U256::from(weight) / U256::from(total_weight)).as_u128();
+    let probability = ONE - exp2_negative(exponent);
+    if probability >= ONE {
+        u128::max_value()
+    } else {
+        probability << (128 - FRACTIONAL_BITS)
+    }
+}
+
+/// Gives `log2(numerator / denominator)` as a fixed point number.
+/// Numerator need to be greater than denominator, and both need to fit in `u64`.
+fn log2_fraction(numerator: u128, denominator: u128) -> u128 {
+    let mut integer: u128 = 0;
+    let mut denominator = denominator;
+    while numerator >= denominator << 1 {
+        denominator <<= 1;
+        integer += 1;
+    }
+
+    // Remaining fraction is in range [1, 2), so its logarithm is found bit by bit,
+    // as squaring it doubles the logarithm.
+    let mut x = (numerator << FRACTIONAL_BITS) / denominator;
+    let mut fraction: u128 = 0;
+    for bit in (0..FRACTIONAL_BITS).rev() {
+        x = (x * x) >> FRACTIONAL_BITS;
+        if x >= 2 * ONE {
+            x >>= 1;
+            fraction |= 1 << bit;
+        }
+    }
+    (integer << FRACTIONAL_BITS) | fraction
+}
+
+/// Gives `2^(-exponent)`, where both are fixed point numbers.
+fn exp2_negative(exponent: u128) -> u128 {
+    let integer = exponent >> FRACTIONAL_BITS;
+    if integer >= FRACTIONAL_BITS as u128 {
+        return 0;
+    }
+
+    // Every bit of fraction multiplies result by 2^(-1/2^n), which is
+    // found by repeatedly taking square root, starting from 2^(-1).
+    let mut result = ONE;
+    let mut root = ONE >> 1;
+    for bit in (0..FRACTIONAL_BITS).rev() {
+        root = integer_sqrt(root << FRACTIONAL_BITS);
+        if exponent & (1 << bit) != 0 {
+            result = (result * root) >> FRACTIONAL_BITS;
+        }
+    }
+    result >> integer
+}
+
+fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// Checks that header is sealed by the author of slot it claims, and that
+/// the claim is valid in the given epoch.
+pub(crate) fn check_header<B: BlockT>(
+    mut header: B::Header,
+    pre_digest: &PreDigest,
+    epoch: &BabeEpoch,
+) -> Result<(), String> {
+    let seal = header
+        .digest_mut()
+        .pop()
+        .ok_or_else(|| "Header is unsealed".to_string())?;
+    let signature: Signature = seal
+        .try_to(OpaqueDigestItemId::Seal(&BABE_ENGINE_ID))
+        .ok_or_else(|| "Header has bad BABE seal".to_string())?;
+    // Seal signs hash of the header without the seal itself
+    let pre_hash = header.hash();
+
+    match pre_digest {
+        PreDigest::Primary(primary) => {
+            check_primary_header(pre_hash.as_ref(), primary, &signature, epoch)
+        }
+        PreDigest::Secondary(secondary) => {
+            check_secondary_header(pre_hash.as_ref(), secondary, &signature, epoch)
+        }
+    }
+}
+
+fn check_primary_header(
+    pre_hash: &[u8],
+    pre_digest: &PrimaryPreDigest,
+    signature: &Signature,
+    epoch: &BabeEpoch,
+) -> Result<(), String> {
+    let authority_index = pre_digest.authority_index as usize;
+    let (author, _) = epoch
+        .authorities
+        .get(authority_index)
+        .ok_or_else(|| format!("Slot author {} is not in BABE epoch", authority_index))?;
+
+    if !Pair::verify(signature, pre_hash, author) {
+        return Err("Bad signature on BABE seal".into());
+    }
+
+    let public_key = schnorrkel::PublicKey::from_bytes(author.as_ref())
+        .map_err(|_| "Slot author has invalid sr25519 key".to_string())?;
+    let vrf_output = VRFOutput::from_bytes(&pre_digest.vrf_output)
+        .map_err(|_| "Primary slot claim has invalid VRF output".to_string())?;
+    let vrf_proof = VRFProof::from_bytes(&pre_digest.vrf_proof)
+        .map_err(|_| "Primary slot claim has invalid VRF proof".to_string())?;
+    let transcript = make_transcript(&epoch.randomness, pre_digest.slot_number, epoch.epoch_index);
+    let (inout, _) = public_key
+        .vrf_verify(transcript, &vrf_output, &vrf_proof)
+        .map_err(|_| "VRF verification of primary slot claim failed".to_string())?;
+
+    let threshold = calculate_primary_threshold(epoch.c, &epoch.authorities, authority_index);
+    if u128::from_le_bytes(inout.make_bytes::<[u8; 16]>(BABE_VRF_PREFIX)) >= threshold {
+        return Err("VRF output of primary slot claim is over the threshold".into());
+    }
+
+    Ok(())
+}
+
+fn check_secondary_header(
+    pre_hash: &[u8],
+    pre_digest: &SecondaryPreDigest,
+    signature: &Signature,
+    epoch: &BabeEpoch,
+) -> Result<(), String> {
+    if !epoch.secondary_slots {
+        return Err("Secondary slots are not allowed in BABE epoch".into());
+    }
+
+    let expected_author =
+        secondary_slot_author(pre_digest.slot_number, &epoch.authorities, epoch.randomness)
+            .ok_or_else(|| "BABE epoch has no authorities".to_string())?;
+    let author = epoch
+        .authorities
+        .get(pre_digest.authority_index as usize)
+        .map(|(author, _)| author);
+    if author != Some(expected_author) {
+        return Err("Secondary slot is assigned to another authority".into());
+    }
+
+    if !Pair::verify(signature, pre_hash, expected_author) {
+        return Err("Bad signature on BABE seal".into());
+    }
+
+    Ok(())
+}
+
+/// Verifies BABE slot claims and seals of headers, which are not justified, and tracks
+/// epochs they announce. Headers are passed to the inner verifier afterwards. Nothing
+/// is verified unless BABE epoch is stored, in which case inner verifier is used alone.
+pub struct BabeVerifier<S, V> {
+    storage: Arc<S>,
+    inner: V,
+}
+
+impl<S, V> BabeVerifier<S, V> {
+    pub fn new(storage: Arc<S>, inner: V) -> Self {
+        Self { storage, inner }
+    }
+}
+
+impl<S, V, Block> Verifier<Block> for BabeVerifier<S, V>
+where
+    S: AuxStore + HeaderBackend<Block>,
+    V: Verifier<Block>,
+    Block: BlockT,
+{
+    fn verify(
+        &mut self,
+        origin: BlockOrigin,
+        header: <Block as BlockT>::Header,
+        justification: Option<Vec<u8>>,
+        body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+    ) -> Result<BlockImportParams<Block>, String> {
+        let epoch = match fetch_babe_epoch(self.storage.clone()).map_err(|e| format!("{}", e))? {
+            Some(epoch) => epoch,
+            None => return self.inner.verify(origin, header, justification, body),
+        };
+        let possible_next_epoch =
+            fetch_next_babe_epoch(self.storage.clone()).map_err(|e| format!("{}", e))?;
+
+        let pre_digest = find_pre_digest::<Block>(&header)
+            .ok_or_else(|| "Header has no BABE pre-runtime digest".to_string())?;
+        let slot_number = pre_digest.slot_number();
+
+        // Parent is unknown if header skips ahead, and has no pre-runtime
+        // digest if it is the initial header of a chain without BABE.
+        let possible_parent_slot_number = self
+            .storage
+            .header(BlockId::<Block>::Hash(*header.parent_hash()))
+            .map_err(|e| format!("{}", e))?
+            .and_then(|parent_header| find_pre_digest::<Block>(&parent_header))
+            .map(|parent_pre_digest| parent_pre_digest.slot_number());
+        if let Some(parent_slot_number) = possible_parent_slot_number {
+            if slot_number <= parent_slot_number {
+                return Err(format!(
+                    "Slot {} of header does not follow slot {} of its parent",
+                    slot_number, parent_slot_number
+                ));
+            }
+        }
+
+        // Header starting the next epoch enacts it.
+        let (possible_header_epoch, possible_enacted_epoch) = if epoch.contains_slot(slot_number) {
+            (Some(epoch.clone()), None)
+        } else {
+            match possible_next_epoch {
+                Some(next_epoch) if next_epoch.contains_slot(slot_number) => {
+                    (Some(next_epoch.clone()), Some(next_epoch))
+                }
+                _ => (None, None),
+            }
+        };
+
+        let possible_announced_epoch = match find_next_epoch_descriptor::<Block>(&header) {
+            Some(descriptor) => {
+                let possible_config =
+                    find_next_config_descriptor::<Block>(&header).map(|config| match config {
+                        NextConfigDescriptor::V1 { c, allowed_slots } => (
+                            c,
+                            match allowed_slots {
+                                AllowedSlots::PrimarySlots => false,
+                                AllowedSlots::PrimaryAndSecondaryPlainSlots => true,
+                            },
+                        ),
+                    });
+                // Justified header may skip ahead of known epochs, in which case its epoch
+                // is found by counting epochs since the current one, as none are skipped.
+                let possible_base_epoch = match possible_header_epoch.as_ref() {
+                    Some(header_epoch) => Some(header_epoch.clone()),
+                    None if slot_number >= epoch.start_slot && epoch.duration > 0 => {
+                        let elapsed_epochs = (slot_number - epoch.start_slot) / epoch.duration;
+                        epoch
+                            .epoch_index
+                            .checked_add(elapsed_epochs)
+                            .and_then(|epoch_index| {
+                                elapsed_epochs
+                                    .checked_mul(epoch.duration)
+                                    .and_then(|slots| epoch.start_slot.checked_add(slots))
+                                    .map(|start_slot| BabeEpoch {
+                                        epoch_index,
+                                        start_slot,
+                                        ..epoch.clone()
+                                    })
+                            })
+                    }
+                    None => None,
+                };
+                match possible_base_epoch {
+                    Some(base_epoch) => Some(
+                        base_epoch
+                            .construct_next_epoch(
+                                descriptor.authorities,
+                                descriptor.randomness,
+                                possible_config,
+                            )
+                            .ok_or("Announced BABE epoch overflows slot number")?,
+                    ),
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
+        // Justified headers are verified by GRANDPA, and only
+        // update epochs, even if their epoch is unknown.
+        if justification.is_none() {
+            let header_epoch = possible_header_epoch.as_ref().ok_or_else(|| {
+                format!(
+                    "Slot {} of header is outside of known BABE epochs",
+                    slot_number
+                )
+            })?;
+            check_header::<Block>(header.clone(), &pre_digest, header_epoch)?;
+            if possible_enacted_epoch.is_some() && possible_announced_epoch.is_none() {
+                return Err("Header starting BABE epoch need to announce the next one".into());
+            }
+        }
+
+        let mut block_import_params = self.inner.verify(origin, header, justification, body)?;
+        if possible_enacted_epoch.is_some() || possible_announced_epoch.is_some() {
+            block_import_params.intermediates.insert(
+                Cow::from(BABE_EPOCH_CHANGES_INTERMEDIATE_KEY),
+                Box::new(BabeEpochChanges {
+                    enacted: possible_enacted_epoch,
+                    announced: possible_announced_epoch,
+                }),
+            );
+        }
+
+        Ok(block_import_params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::babe_verifier::calculate_primary_threshold;
+    use sp_core::{sr25519, Pair};
+
+    // Threshold as calculated upstream, using floating point arithmetic
+    fn expected_threshold(c: (u64, u64), weight: u64, total_weight: u64) -> f64 {
+        let c = c.0 as f64 / c.1 as f64;
+        let theta = weight as f64 / total_weight as f64;
+        (1f64 - (1f64 - c).powf(theta)) * 2f64.powi(128)
+    }
+
+    #[test]
+    fn primary_threshold_approximates_floating_point_calculation() {
+        let authorities: Vec<_> = (1..=4u8)
+            .map(|seed| (sr25519::Pair::from_seed(&[seed; 32]).public(), seed as u64))
+            .collect();
+        for c in &[(1, 4), (1, 2), (3, 4), (1, 1000), (999, 1000)] {
+            for (index, (_, weight)) in authorities.iter().enumerate() {
+                let threshold = calculate_primary_threshold(*c, &authorities, index) as f64;
+                let expected = expected_threshold(*c, *weight, 10);
+                assert!(
+                    (threshold - expected).abs() / expected < 1e-9,
+                    "Threshold {} differs from {} for c {:?} and authority {}",
+                    threshold,
+                    expected,
+                    c,
+                    index
+                );
+            }
+        }
+
+        assert_eq!(calculate_primary_threshold((0, 1), &authorities, 0), 0);
+        assert_eq!(
+            calculate_primary_threshold((1, 1), &authorities, 0),
+            u128::max_value()
+        );
+        // Authority outside of the epoch can not claim primary slots
+        assert_eq!(calculate_primary_threshold((1, 4), &authorities, 4), 0);
+    }
+}
@@ -0,0 +1,379 @@
+use parity_scale_codec::{Decode, Encode};
+use sp_core::hashing::keccak_256;
+use sp_core::{ecdsa, H256};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+use crate::common::types::beefy_validator_set::{BeefyAuthorityId, BeefyNextAuthoritySet};
+use crate::common::types::blockchain_error::BlockchainError;
+use crate::common::types::blockchain_result::BlockchainResult;
+use crate::justification::ProvableJustification;
+
+/// MMR proofs covering more leaves than this are rejected, so that
+/// positions within the MMR can be calculated without overflowing.
+const MAX_MMR_LEAF_COUNT: u64 = 1 << 60;
+
+/// Mirrors `Commitment` of `beefy-primitives`, with MMR root as its payload.
+#[derive(Encode, Decode)]
+pub struct Commitment<Block: BlockT> {
+    pub payload: H256,
+    pub block_number: NumberFor<Block>,
+    pub validator_set_id: u64,
+}
+
+/// Commitment along with signatures of validators, which are
+/// listed in the same order as validators of the set.
+#[derive(Encode, Decode)]
+pub struct SignedCommitment<Block: BlockT> {
+    pub commitment: Commitment<Block>,
+    pub signatures: Vec<Option<ecdsa::Signature>>,
+}
+
+impl<Block: BlockT> SignedCommitment<Block> {
+    /// Checks that more than 2/3 of validators signed keccak hash of the encoded commitment.
+    pub fn verify(&self, set_id: u64, validators: &[BeefyAuthorityId]) -> BlockchainResult<()> {
+        if self.commitment.validator_set_id != set_id {
+            return Err(BlockchainError::BadJustification(format!(
+                "Commitment is signed by BEEFY validator set {}, expected {}",
+                self.commitment.validator_set_id, set_id
+            )));
+        }
+        if validators.is_empty() {
+            return Err(BlockchainError::InvalidAuthoritiesSet);
+        }
+        if self.signatures.len() != validators.len() {
+            return Err(BlockchainError::BadJustification(format!(
+                "Commitment carries {} signatures for {} BEEFY validators",
+                self.signatures.len(),
+                validators.len()
+            )));
+        }
+
+        let message = keccak_256(&self.commitment.encode());
+        let mut signed = 0;
+        for (index, (possible_signature, validator)) in
+            self.signatures.iter().zip(validators).enumerate()
+        {
+            if let Some(signature) = possible_signature {
+                if recover_signer(signature, &message).as_ref() != Some(validator) {
+                    return Err(BlockchainError::BadJustification(format!(
+                        "Bad signature of BEEFY validator {}",
+                        index
+                    )));
+                }
+                signed += 1;
+            }
+        }
+
+        let threshold = signature_threshold(validators.len());
+        if signed < threshold {
+            return Err(BlockchainError::BadJustification(format!(
+                "Commitment is signed by {} BEEFY validators, at least {} are required",
+                signed, threshold
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Mirrors `MmrLeaf` of `beefy-primitives`. Leaf is added to the MMR in every block,
+/// and commits to its parent along with the next BEEFY validator set.
+#[derive(Encode, Decode)]
+pub struct MmrLeaf<Block: BlockT> {
+    pub parent_number_and_hash: (NumberFor<Block>, Block::Hash),
+    pub beefy_next_authority_set: BeefyNextAuthoritySet,
+    pub parachain_heads: H256,
+}
+
+/// Mirrors `Proof` of `pallet-mmr`, which proves a single leaf.
+#[derive(Encode, Decode)]
+pub struct MmrLeafProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    pub items: Vec<H256>,
+}
+
+/// BEEFY counterpart of GRANDPA justification. Header is finalized by the MMR leaf
+/// committing to it, once the leaf is proven to be in the MMR signed by validators.
+#[derive(Encode, Decode)]
+pub struct BeefyFinalityProof<Block: BlockT> {
+    pub signed_commitment: SignedCommitment<Block>,
+    pub leaf: MmrLeaf<Block>,
+    pub leaf_proof: MmrLeafProof,
+}
+
+impl<Block: BlockT> ProvableJustification<Block, BeefyAuthorityId> for BeefyFinalityProof<Block> {
+    fn verify(&self, set_id: u64, authorities: &[BeefyAuthorityId]) -> BlockchainResult<()> {
+        self.signed_commitment.verify(set_id, authorities)?;
+
+        let leaf_hash = H256(keccak_256(&self.leaf.encode()));
+        match mmr_root(leaf_hash, &self.leaf_proof) {
+            Some(root) if root == self.signed_commitment.commitment.payload => Ok(()),
+            _ => Err(BlockchainError::BadJustification(
+                "MMR leaf is not proven to be in the signed MMR".into(),
+            )),
+        }
+    }
+
+    fn verify_finalization(
+        &self,
+        set_id: u64,
+        finalized_target: (Block::Hash, NumberFor<Block>),
+        authorities: &[BeefyAuthorityId],
+    ) -> BlockchainResult<()> {
+        let (hash, number) = finalized_target;
+        if self.leaf.parent_number_and_hash != (number, hash) {
+            return Err(BlockchainError::BadJustification(format!(
+                "MMR leaf does not commit to header {}",
+                number
+            )));
+        }
+        self.verify(set_id, authorities)
+    }
+}
+
+/// Gives number of signatures needed for a commitment to be valid.
+pub fn signature_threshold(validators: usize) -> usize {
+    validators - validators.saturating_sub(1) / 3
+}
+
+/// Gives root of binary merkle tree over keccak hashes of validator public keys, in which
+/// the last node of a level with odd number of nodes is promoted to the level above.
+pub fn validators_merkle_root(validators: &[BeefyAuthorityId]) -> H256 {
+    let mut nodes: Vec<[u8; 32]> = validators
+        .iter()
+        .map(|validator| keccak_256(validator.as_ref()))
+        .collect();
+    if nodes.is_empty() {
+        return H256::zero();
+    }
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| {
+                if pair.len() == 2 {
+                    keccak_256(&[pair[0], pair[1]].concat())
+                } else {
+                    pair[0]
+                }
+            })
+            .collect();
+    }
+    H256(nodes[0])
+}
+
+fn recover_signer(signature: &ecdsa::Signature, message: &[u8; 32]) -> Option<BeefyAuthorityId> {
+    let raw: &[u8; 65] = signature.as_ref();
+    let rs = secp256k1::Signature::parse_slice(&raw[..64]).ok()?;
+    let recovery_id = secp256k1::RecoveryId::parse(raw[64]).ok()?;
+    secp256k1::recover(&secp256k1::Message::parse(message), &rs, &recovery_id)
+        .ok()
+        .map(|public| ecdsa::Public::from_raw(public.serialize_compressed()))
+}
+
+/// Gives root of the MMR, which the leaf is proven to be in, or `None` if proof is malformed.
+/// Positions and bagging of peaks follow `ckb-merkle-mountain-range` used by `pallet-mmr`.
+pub fn mmr_root(leaf_hash: H256, proof: &MmrLeafProof) -> Option<H256> {
+    if proof.leaf_index >= proof.leaf_count || proof.leaf_count > MAX_MMR_LEAF_COUNT {
+        return None;
+    }
+    let mmr_size = leaf_index_to_mmr_size(proof.leaf_count - 1);
+    let leaf_pos = leaf_index_to_pos(proof.leaf_index);
+    let mut items = proof.items.iter();
+
+    let mut possible_leaf = Some((leaf_pos, leaf_hash));
+    let mut peak_hashes = vec![];
+    for peak_pos in get_peaks(mmr_size) {
+        let peak_hash = match possible_leaf {
+            Some((pos, hash)) if pos <= peak_pos => {
+                possible_leaf = None;
+                calculate_peak_root(pos, hash, peak_pos, &mut items)?
+            }
+            // Peaks left of the leaf are supplied as they are, while the ones right of it are bagged
+            _ => match items.next() {
+                Some(hash) => *hash,
+                None => break,
+            },
+        };
+        peak_hashes.push(peak_hash);
+    }
+    if possible_leaf.is_some() || items.next().is_some() {
+        return None;
+    }
+
+    let mut root = peak_hashes.pop()?;
+    while let Some(left_peak) = peak_hashes.pop() {
+        root = merge(&root, &left_peak);
+    }
+    Some(root)
+}
+
+fn calculate_peak_root<'a>(
+    mut pos: u64,
+    mut hash: H256,
+    peak_pos: u64,
+    items: &mut impl Iterator<Item = &'a H256>,
+) -> Option<H256> {
+    let mut height = 0;
+    while pos < peak_pos {
+        let sibling = items.next()?;
+        if pos_height_in_tree(pos + 1) > height {
+            // Node is the right child, as its parent follows it
+            pos += 1;
+            hash = merge(sibling, &hash);
+        } else {
+            pos += parent_offset(height);
+            hash = merge(&hash, sibling);
+        }
+        height += 1;
+    }
+    if pos == peak_pos {
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+fn merge(left: &H256, right: &H256) -> H256 {
+    H256(keccak_256(&[left.as_bytes(), right.as_bytes()].concat()))
+}
+
+fn leaf_index_to_pos(index: u64) -> u64 {
+    leaf_index_to_mmr_size(index) - u64::from((index + 1).trailing_zeros()) - 1
+}
+
+fn leaf_index_to_mmr_size(index: u64) -> u64 {
+    let leaves_count = index + 1;
+    2 * leaves_count - u64::from(leaves_count.count_ones())
+}
+
+fn pos_height_in_tree(pos: u64) -> u32 {
+    let mut pos = pos + 1;
+    while !(pos != 0 && pos.count_zeros() == pos.leading_zeros()) {
+        let most_significant_bit = 1u64 << (63 - pos.leading_zeros());
+        pos -= most_significant_bit - 1;
+    }
+    63 - pos.leading_zeros()
+}
+
+fn parent_offset(height: u32) -> u64 {
+    2 << height
+}
+
+fn sibling_offset(height: u32) -> u64 {
+    (2 << height) - 1
+}
+
+fn get_peaks(mmr_size: u64) -> Vec<u64> {
+    let (mut height, mut pos) = left_peak_height_pos(mmr_size);
+    let mut positions = vec![pos];
+    while height > 0 {
+        match get_right_peak(height, pos, mmr_size) {
+            Some((right_height, right_pos)) => {
+                height = right_height;
+                pos = right_pos;
+                positions.push(pos);
+            }
+            None => break,
+        }
+    }
+    positions
+}
+
+fn left_peak_height_pos(mmr_size: u64) -> (u32, u64) {
+    let peak_pos_by_height = |height: u32| (1u64 << (height + 1)) - 2;
+    let mut height = 1;
+    let mut prev_pos = 0;
+    let mut pos = peak_pos_by_height(height);
+    while pos < mmr_size {
+        height += 1;
+        prev_pos = pos;
+        pos = peak_pos_by_height(height);
+    }
+    (height - 1, prev_pos)
+}
+
+fn get_right_peak(mut height: u32, mut pos: u64, mmr_size: u64) -> Option<(u32, u64)> {
+    pos += sibling_offset(height);
+    while pos > mmr_size - 1 {
+        if height == 0 {
+            return None;
+        }
+        pos -= parent_offset(height - 1);
+        height -= 1;
+    }
+    Some((height, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beefy::{merge, mmr_root, validators_merkle_root, MmrLeafProof};
+    use sp_core::hashing::keccak_256;
+    use sp_core::{ecdsa, H256};
+
+    #[test]
+    fn mmr_root_is_calculated_from_leaf_proofs() {
+        let leaves: Vec<H256> = (0..7u8).map(|i| H256::repeat_byte(i)).collect();
+        // MMR of 7 leaves has peaks of 4, 2 and 1 leaves
+        let left_peak = merge(
+            &merge(&leaves[0], &leaves[1]),
+            &merge(&leaves[2], &leaves[3]),
+        );
+        let middle_peak = merge(&leaves[4], &leaves[5]);
+        let right_peak = leaves[6];
+        let root = merge(&merge(&right_peak, &middle_peak), &left_peak);
+
+        let proof = |leaf_index: u64, items: Vec<H256>| MmrLeafProof {
+            leaf_index,
+            leaf_count: 7,
+            items,
+        };
+        let right_peaks = merge(&right_peak, &middle_peak);
+        assert_eq!(
+            mmr_root(
+                leaves[2],
+                &proof(
+                    2,
+                    vec![leaves[3], merge(&leaves[0], &leaves[1]), right_peaks]
+                )
+            ),
+            Some(root)
+        );
+        assert_eq!(
+            mmr_root(leaves[5], &proof(5, vec![left_peak, leaves[4], right_peak])),
+            Some(root)
+        );
+        assert_eq!(
+            mmr_root(leaves[6], &proof(6, vec![left_peak, middle_peak])),
+            Some(root)
+        );
+
+        // Proofs with missing, extra or misplaced items are rejected
+        assert_ne!(
+            mmr_root(leaves[6], &proof(6, vec![middle_peak, left_peak])),
+            Some(root)
+        );
+        assert_eq!(mmr_root(leaves[6], &proof(6, vec![left_peak])), None);
+        assert_eq!(
+            mmr_root(
+                leaves[6],
+                &proof(6, vec![left_peak, middle_peak, right_peak])
+            ),
+            None
+        );
+        assert_eq!(mmr_root(leaves[6], &proof(7, vec![])), None);
+    }
+
+    #[test]
+    fn validators_merkle_root_promotes_odd_nodes() {
+        let validators: Vec<ecdsa::Public> = (1..=3u8)
+            .map(|i| ecdsa::Public::from_raw([i; 33]))
+            .collect();
+        let hashes: Vec<[u8; 32]> = validators
+            .iter()
+            .map(|validator| keccak_256(validator.as_ref()))
+            .collect();
+        let root = keccak_256(&[keccak_256(&[hashes[0], hashes[1]].concat()), hashes[2]].concat());
+        assert_eq!(validators_merkle_root(&validators), H256(root));
+        assert_eq!(validators_merkle_root(&validators[..1]), H256(hashes[0]));
+    }
+}
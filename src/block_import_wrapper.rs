@@ -1,12 +1,17 @@
 use crate::common::traits::aux_store::AuxStore;
 use crate::common::traits::block_import::BlockImport;
+use crate::common::types::aura_authority_set::AuraAuthoritySet;
+use crate::common::types::babe_epoch::BabeEpochChanges;
 use crate::common::types::block_check_params::BlockCheckParams;
 use crate::common::types::block_import_params::BlockImportParams;
 use crate::common::types::consensus_error::ConsensusError;
 use crate::common::types::import_result::ImportResult;
 use crate::common::types::next_change_in_authority::NextChangeInAuthority;
 use crate::common::utils::{
-    store_next_authority_change, GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY,
+    delete_next_babe_epoch, insert_aura_authority_set, insert_babe_epoch, insert_next_babe_epoch,
+    store_next_authority_change, AURA_AUTHORITIES_CHANGE_INTERMEDIATE_KEY,
+    BABE_EPOCH_CHANGES_INTERMEDIATE_KEY, GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY,
+    GRANDPA_FORCED_AUTHORITY_CHANGE_INTERMEDIATE_KEY,
 };
 use parity_scale_codec::alloc::sync::Arc;
 use sp_runtime::traits::Block as BlockT;
@@ -44,16 +49,12 @@ where
         &mut self,
         mut block: BlockImportParams<Block>,
     ) -> Result<ImportResult, Self::Error> {
-        let possible_next_change_in_authority = match block
-            .take_intermediate::<NextChangeInAuthority<Block>>(
-                GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY,
-            ) {
-            Err(e) => match e {
-                Self::Error::NoIntermediate => Ok(None),
-                _ => Err(e),
-            },
-            Ok(next_change_in_authority) => Ok(Some(next_change_in_authority)),
-        }?;
+        let possible_next_change_in_authority =
+            take_authority_change(&mut block, GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY)?;
+        let possible_next_forced_change_in_authority =
+            take_authority_change(&mut block, GRANDPA_FORCED_AUTHORITY_CHANGE_INTERMEDIATE_KEY)?;
+        let possible_babe_epoch_changes = take_babe_epoch_changes(&mut block)?;
+        let possible_next_aura_authority_set = take_aura_authorities_change(&mut block)?;
 
         let result = self.wrapped_block_import.import_block(block);
 
@@ -64,12 +65,80 @@ where
             _ => false,
         };
 
-        if should_store_next_authority_change && possible_next_change_in_authority.is_some() {
-            let next_change_in_authority = possible_next_change_in_authority.unwrap();
-            store_next_authority_change(self.aux_store.clone(), next_change_in_authority.deref())
+        if should_store_next_authority_change {
+            for next_change_in_authority in possible_next_change_in_authority
+                .iter()
+                .chain(possible_next_forced_change_in_authority.iter())
+            {
+                store_next_authority_change(
+                    self.aux_store.clone(),
+                    next_change_in_authority.deref(),
+                )
                 .map_err(|err| Self::Error::Other(Box::new(err)))?;
+            }
+            if let Some(babe_epoch_changes) = possible_babe_epoch_changes {
+                if let Some(enacted_epoch) = babe_epoch_changes.enacted.as_ref() {
+                    insert_babe_epoch(self.aux_store.clone(), enacted_epoch)
+                        .and_then(|_| delete_next_babe_epoch(self.aux_store.clone()))
+                        .map_err(|err| Self::Error::Other(Box::new(err)))?;
+                }
+                if let Some(announced_epoch) = babe_epoch_changes.announced.as_ref() {
+                    insert_next_babe_epoch(self.aux_store.clone(), announced_epoch)
+                        .map_err(|err| Self::Error::Other(Box::new(err)))?;
+                }
+            }
+            if let Some(next_aura_authority_set) = possible_next_aura_authority_set {
+                insert_aura_authority_set(self.aux_store.clone(), next_aura_authority_set.deref())
+                    .map_err(|err| Self::Error::Other(Box::new(err)))?;
+            }
         }
 
         result
     }
 }
+
+fn take_authority_change<Block>(
+    block: &mut BlockImportParams<Block>,
+    key: &[u8],
+) -> Result<Option<Box<NextChangeInAuthority<Block>>>, ConsensusError>
+where
+    Block: BlockT,
+{
+    match block.take_intermediate::<NextChangeInAuthority<Block>>(key) {
+        Err(e) => match e {
+            ConsensusError::NoIntermediate => Ok(None),
+            _ => Err(e),
+        },
+        Ok(next_change_in_authority) => Ok(Some(next_change_in_authority)),
+    }
+}
+
+fn take_babe_epoch_changes<Block>(
+    block: &mut BlockImportParams<Block>,
+) -> Result<Option<Box<BabeEpochChanges>>, ConsensusError>
+where
+    Block: BlockT,
+{
+    match block.take_intermediate::<BabeEpochChanges>(BABE_EPOCH_CHANGES_INTERMEDIATE_KEY) {
+        Err(e) => match e {
+            ConsensusError::NoIntermediate => Ok(None),
+            _ => Err(e),
+        },
+        Ok(babe_epoch_changes) => Ok(Some(babe_epoch_changes)),
+    }
+}
+
+fn take_aura_authorities_change<Block>(
+    block: &mut BlockImportParams<Block>,
+) -> Result<Option<Box<AuraAuthoritySet>>, ConsensusError>
+where
+    Block: BlockT,
+{
+    match block.take_intermediate::<AuraAuthoritySet>(AURA_AUTHORITIES_CHANGE_INTERMEDIATE_KEY) {
+        Err(e) => match e {
+            ConsensusError::NoIntermediate => Ok(None),
+            _ => Err(e),
+        },
+        Ok(next_aura_authority_set) => Ok(Some(next_aura_authority_set)),
+    }
+}
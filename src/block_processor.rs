@@ -1,3 +1,5 @@
+use crate::aura_verifier::AuraVerifier;
+use crate::babe_verifier::BabeVerifier;
 use crate::block_import_wrapper::BlockImportWrapper;
 use crate::client::Client;
 use crate::common::traits::block_import::BlockImport;
@@ -11,21 +13,28 @@ use crate::common::types::consensus_error::ConsensusError;
 use crate::common::types::import_result::ImportResult;
 use crate::common::types::incoming_block::IncomingBlock;
 use crate::common::utils::initialize_storage;
-use crate::db;
 use crate::grandpa_block_import::GrandpaLightBlockImport;
-use crate::types::Block;
 use crate::verifier::GrandpaVerifier;
-use sp_runtime::traits::{Block as BlockT, Header, NumberFor};
+use finality_grandpa::BlockNumberOps;
+use kvdb::KeyValueDB;
+use parity_scale_codec::Encode;
+use sp_runtime::traits::{Block as BlockT, DigestFor, Header, NumberFor};
 use std::sync::Arc;
 
-pub type BlockProcessor<B> =
-    Box<dyn FnMut(IncomingBlock<B>) -> Result<BlockImportResult<NumberFor<B>>, String>>;
+pub type BlockProcessor<'a, B> =
+    Box<dyn FnMut(IncomingBlock<B>) -> Result<BlockImportResult<NumberFor<B>>, String> + 'a>;
 
-pub fn setup_block_processor(
-    encoded_data: Vec<u8>,
+pub fn setup_block_processor<'a, Block, D>(
+    db: D,
     max_non_finalized_blocks_allowed: u64,
-) -> BlockchainResult<(BlockProcessor<Block>, db::Data)> {
-    let (data, storage) = initialize_storage(encoded_data, max_non_finalized_blocks_allowed)?;
+) -> BlockchainResult<BlockProcessor<'a, Block>>
+where
+    Block: BlockT,
+    NumberFor<Block>: BlockNumberOps,
+    DigestFor<Block>: Encode,
+    D: KeyValueDB + 'a,
+{
+    let storage = initialize_storage(db, max_non_finalized_blocks_allowed)?;
 
     // Custom client implementation with dummy runtime
     let client = Arc::new(Client::new(storage.clone()));
@@ -35,19 +44,23 @@ pub fn setup_block_processor(
     // auxiliary storage.
     let block_processor_fn = Box::new(move |incoming_block: IncomingBlock<Block>| {
         let grandpa_block_import = GrandpaLightBlockImport::new(client.clone(), storage.clone());
-        let mut grandpa_verifier = GrandpaVerifier::new(storage.clone());
+        let grandpa_verifier = GrandpaVerifier::new(storage.clone());
+        // BABE and Aura seals are only verified if BABE epoch
+        // or Aura authority set is stored, respectively
+        let babe_verifier = BabeVerifier::new(storage.clone(), grandpa_verifier);
+        let mut aura_verifier = AuraVerifier::new(storage.clone(), babe_verifier);
         let mut block_import_wrapper: BlockImportWrapper<_, _> =
             BlockImportWrapper::new(grandpa_block_import.clone(), storage.clone());
         import_single_block(
             &mut block_import_wrapper,
             BlockOrigin::NetworkBroadcast,
             incoming_block,
-            &mut grandpa_verifier,
+            &mut aura_verifier,
         )
         .map_err(|e| format!("{:?}", e))
     });
 
-    Ok((block_processor_fn, data))
+    Ok(block_processor_fn)
 }
 
 /// Single block import function.
@@ -81,6 +94,7 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
         number,
         parent_hash,
         allow_missing_state: block.allow_missing_state,
+        allow_gap: block.allow_gap,
         import_existing: block.import_existing,
     }))? {
         BlockImportResult::ImportedUnknown { .. } => (),
@@ -92,6 +106,7 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
         .map_err(|msg| BlockImportError::VerificationFailed(msg))?;
 
     import_block.allow_missing_state = block.allow_missing_state;
+    import_block.allow_gap = block.allow_gap;
 
     import_error(import_handle.import_block(import_block))
 }
@@ -253,6 +253,7 @@ where
             number,
             parent_hash,
             allow_missing_state,
+            allow_gap,
             import_existing,
         } = block;
 
@@ -284,6 +285,7 @@ where
             .map_err(|e| ConsensusError::ClientImport(e.to_string()))?
         {
             ImportBlockStatus::InChainWithState | ImportBlockStatus::Queued => {}
+            ImportBlockStatus::Unknown if allow_gap => {}
             ImportBlockStatus::Unknown => return Ok(ImportResult::UnknownParent),
             ImportBlockStatus::InChainPruned if allow_missing_state => {}
             ImportBlockStatus::InChainPruned => return Ok(ImportResult::MissingState),
@@ -302,6 +304,7 @@ where
             justification: _,
             auxiliary: _,
             fork_choice: _,
+            finalized,
             intermediates,
             import_existing: _,
             ..
@@ -323,8 +326,14 @@ where
             BlockStatus::Unknown => {}
         }
 
+        let state = if finalized {
+            NewBlockState::Final
+        } else {
+            NewBlockState::Best
+        };
+
         self.storage
-            .import_header(header, NewBlockState::Best)
+            .import_header(header, state)
             .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
 
         Ok(ImportResult::imported(true))
@@ -388,11 +397,16 @@ where
 
         // Since we do not allow forks, retracted always needs to be empty and
         // enacted always need to be non-empty
-        assert!(route_to_be_finalized.retracted().is_empty());
-        assert!(!route_to_be_finalized.enacted().is_empty());
-
+        if !route_to_be_finalized.retracted().is_empty() {
+            return Err(BlockchainError::NotInFinalizedChain);
+        }
         let enacted = route_to_be_finalized.enacted();
-        assert!(enacted.len() > 0);
+        if enacted.last().map(|e| e.hash) != Some(to_be_finalized) {
+            return Err(BlockchainError::NonSequentialFinalization(format!(
+                "Block: {:?} to be finalized is not a descendant of the last finalized block",
+                id
+            )));
+        }
 
         if first_set_of_blocks_to_be_finalized {
             self.storage
@@ -404,8 +418,6 @@ where
                 .finalize_header(BlockId::Hash(finalize_new.hash))?;
         }
 
-        assert_eq!(enacted.last().map(|e| e.hash), Some(to_be_finalized));
-
         self.storage
             .finalize_header(BlockId::Hash(to_be_finalized))?;
 
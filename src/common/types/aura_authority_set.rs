@@ -0,0 +1,50 @@
+use parity_scale_codec::{Decode, Encode};
+use sp_core::{ed25519, sr25519, Pair};
+
+/// Signature scheme of Aura authorities.
+#[derive(Debug, Encode, Decode, Clone, Copy, PartialEq)]
+pub enum AuraCrypto {
+    Sr25519,
+    Ed25519,
+}
+
+/// Aura authorities, which take turns sealing headers of their slots.
+#[derive(Debug, Encode, Decode, Clone, PartialEq)]
+pub struct AuraAuthoritySet {
+    pub crypto: AuraCrypto,
+    /// Public keys of authorities, which are 32 bytes long in both schemes.
+    pub authorities: Vec<[u8; 32]>,
+}
+
+impl AuraAuthoritySet {
+    pub fn new(crypto: AuraCrypto, authorities: Vec<[u8; 32]>) -> Self {
+        Self {
+            crypto,
+            authorities,
+        }
+    }
+
+    /// Gives authority assigned to the slot, or `None` if there are no authorities.
+    pub fn slot_author(&self, slot_number: u64) -> Option<&[u8; 32]> {
+        if self.authorities.is_empty() {
+            return None;
+        }
+        let idx = slot_number % (self.authorities.len() as u64);
+        self.authorities.get(idx as usize)
+    }
+
+    pub fn verify(&self, signature: &[u8; 64], message: &[u8], author: &[u8; 32]) -> bool {
+        match self.crypto {
+            AuraCrypto::Sr25519 => sr25519::Pair::verify(
+                &sr25519::Signature::from_raw(*signature),
+                message,
+                &sr25519::Public::from_raw(*author),
+            ),
+            AuraCrypto::Ed25519 => ed25519::Pair::verify(
+                &ed25519::Signature::from_raw(*signature),
+                message,
+                &ed25519::Public::from_raw(*author),
+            ),
+        }
+    }
+}
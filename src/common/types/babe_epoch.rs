@@ -0,0 +1,59 @@
+use parity_scale_codec::{Decode, Encode};
+use sp_core::sr25519::Public;
+
+/// BABE authorities along with their weights.
+pub type BabeAuthorityList = Vec<(Public, u64)>;
+
+/// BABE epoch, against which slot claims and seals of headers are verified.
+#[derive(Debug, Encode, Decode, Clone, PartialEq)]
+pub struct BabeEpoch {
+    pub epoch_index: u64,
+    pub start_slot: u64,
+    pub duration: u64,
+    pub authorities: BabeAuthorityList,
+    pub randomness: [u8; 32],
+    /// Probability of a slot being empty, as a fraction.
+    pub c: (u64, u64),
+    pub secondary_slots: bool,
+}
+
+impl BabeEpoch {
+    /// Gives epoch announced while this one is in effect, or `None` if epoch index
+    /// or start slot would overflow. Configuration is kept unless it is changed.
+    pub fn construct_next_epoch(
+        &self,
+        authorities: BabeAuthorityList,
+        randomness: [u8; 32],
+        possible_config: Option<((u64, u64), bool)>,
+    ) -> Option<Self> {
+        let (c, secondary_slots) = possible_config.unwrap_or((self.c, self.secondary_slots));
+        Some(Self {
+            epoch_index: self.epoch_index.checked_add(1)?,
+            start_slot: self.start_slot.checked_add(self.duration)?,
+            duration: self.duration,
+            authorities,
+            randomness,
+            c,
+            secondary_slots,
+        })
+    }
+
+    /// Last slot which belongs to the epoch.
+    pub fn end_slot(&self) -> u64 {
+        self.start_slot
+            .saturating_add(self.duration)
+            .saturating_sub(1)
+    }
+
+    pub fn contains_slot(&self, slot_number: u64) -> bool {
+        self.start_slot <= slot_number && slot_number <= self.end_slot()
+    }
+}
+
+/// Epochs to be stored once header carrying them is imported.
+pub struct BabeEpochChanges {
+    /// Epoch which took effect at the header, replacing the current one.
+    pub enacted: Option<BabeEpoch>,
+    /// Epoch announced by the header.
+    pub announced: Option<BabeEpoch>,
+}
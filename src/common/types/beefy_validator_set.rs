@@ -0,0 +1,34 @@
+use parity_scale_codec::{Decode, Encode};
+use sp_core::{ecdsa, H256};
+
+/// BEEFY validators sign commitments with secp256k1 ECDSA keys.
+pub type BeefyAuthorityId = ecdsa::Public;
+
+/// BEEFY validator set, against which signed commitments are verified.
+#[derive(Debug, Encode, Decode, Clone, PartialEq)]
+pub struct BeefyValidatorSet {
+    pub id: u64,
+    pub validators: Vec<BeefyAuthorityId>,
+}
+
+impl BeefyValidatorSet {
+    pub fn new(id: u64, validators: Vec<BeefyAuthorityId>) -> Self {
+        Self { id, validators }
+    }
+}
+
+/// Mirrors `BeefyNextAuthoritySet` of MMR leaves. Validators themselves
+/// are only committed to by the merkle root of their public keys.
+#[derive(Debug, Encode, Decode, Clone, PartialEq)]
+pub struct BeefyNextAuthoritySet {
+    pub id: u64,
+    pub len: u32,
+    pub root: H256,
+}
+
+/// MMR root signed by BEEFY validators in the commitment for given block.
+#[derive(Debug, Encode, Decode, Clone, PartialEq)]
+pub struct BeefyMmrRoot<N> {
+    pub block_number: N,
+    pub root: H256,
+}
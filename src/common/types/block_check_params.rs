@@ -12,6 +12,9 @@ pub struct BlockCheckParams<Block: BlockT> {
     pub parent_hash: Block::Hash,
     /// Allow importing the block skipping state verification if parent state is missing.
     pub allow_missing_state: bool,
+    /// Allow importing the finalized block even if its parent is unknown, skipping
+    /// headers in between.
+    pub allow_gap: bool,
     /// Re-validate existing block.
     pub import_existing: bool,
 }
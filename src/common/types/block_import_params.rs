@@ -41,8 +41,14 @@ pub struct BlockImportParams<Block: BlockT> {
     /// to modify it. If `None` is passed all the way down to bottom block
     /// importer, the import fails with an `IncompletePipeline` error.
     pub fork_choice: Option<ForkChoiceStrategy>,
+    /// Is this block finalized already?
+    /// `true` implies instant finality.
+    pub finalized: bool,
     /// Allow importing the block skipping state verification if parent state is missing.
     pub allow_missing_state: bool,
+    /// Allow importing the finalized block even if its parent is unknown, skipping
+    /// headers in between.
+    pub allow_gap: bool,
     /// Re-validate existing block.
     pub import_existing: bool,
 }
@@ -59,7 +65,9 @@ where
             intermediates: HashMap::new(),
             auxiliary: vec![],
             fork_choice: None,
+            finalized: false,
             allow_missing_state: false,
+            allow_gap: false,
             import_existing: false,
         }
     }
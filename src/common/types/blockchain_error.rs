@@ -34,6 +34,10 @@ pub enum BlockchainError {
     Msg(String),
     /// Error while decoding data
     DataDecode(String),
+    /// Storage proof does not contain nodes required to read the key.
+    InvalidStorageProof(String),
+    /// Storage is full and its oldest header can not be pruned.
+    StorageFull(String),
 }
 
 impl error::Error for BlockchainError {
@@ -96,6 +100,8 @@ impl Display for BlockchainError {
             BlockchainError::IncompletePipeline => write!(f, "Incomplete block import pipeline."),
             BlockchainError::Msg(s) => write!(f, "{}", s),
             BlockchainError::DataDecode(s) => write!(f, "Error while decoding data: {}", s),
+            BlockchainError::InvalidStorageProof(s) => write!(f, "Invalid storage proof: {}", s),
+            BlockchainError::StorageFull(s) => write!(f, "Storage is full: {}", s),
         }
     }
 }
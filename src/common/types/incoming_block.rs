@@ -14,6 +14,9 @@ pub struct IncomingBlock<B: BlockT> {
     pub justification: Option<Justification>,
     /// Allow importing the block skipping state verification if parent state is missing.
     pub allow_missing_state: bool,
+    /// Allow importing the finalized block even if its parent is unknown, skipping
+    /// headers in between.
+    pub allow_gap: bool,
     /// Re-validate existing block.
     pub import_existing: bool,
 }
@@ -0,0 +1,149 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Inconsistency found while checking integrity of light client storage.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityViolation<N, H> {
+    /// Storage metadata is missing.
+    MissingMeta,
+    /// Storage metadata can't be read or decoded.
+    InvalidMeta(String),
+    /// Header expected to be stored is missing or can't be decoded.
+    MissingHeader { hash: H },
+    /// Header is stored under hash other than its own.
+    HeaderHashMismatch { hash: H },
+    /// Number of header doesn't follow the one of header preceding it.
+    BrokenParentLink { number: N, hash: H },
+    /// Lookup entry of stored header is missing or points to another header.
+    LookupMismatch { number: N, hash: H },
+    /// Number of headers found differs from number of headers recorded as stored.
+    TotalStoredMismatch { expected: u64, found: u64 },
+    /// Oldest header found differs from the one recorded as oldest.
+    OldestStoredMismatch { expected: H, found: H },
+    /// Best header recorded in metadata differs from the stored one.
+    BestPointerMismatch { number: N, hash: H },
+    /// Finalized header recorded in metadata isn't stored or differs from the stored one.
+    FinalizedPointerMismatch { number: N, hash: H },
+    /// Header retained as checkpoint is missing or isn't reachable by its number.
+    MissingCheckpoint { number: N, hash: H },
+    /// Value under aux key is missing or can't be decoded.
+    InvalidAuxValue { key: Vec<u8>, reason: String },
+    /// Header signalling pending authority set change is missing.
+    MissingChangeSignallingHeader { key: Vec<u8>, number: N, hash: H },
+}
+
+impl<N, H> IntegrityViolation<N, H> {
+    /// Short name of the kind of violation.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IntegrityViolation::MissingMeta => "missing_meta",
+            IntegrityViolation::InvalidMeta(_) => "invalid_meta",
+            IntegrityViolation::MissingHeader { .. } => "missing_header",
+            IntegrityViolation::HeaderHashMismatch { .. } => "header_hash_mismatch",
+            IntegrityViolation::BrokenParentLink { .. } => "broken_parent_link",
+            IntegrityViolation::LookupMismatch { .. } => "lookup_mismatch",
+            IntegrityViolation::TotalStoredMismatch { .. } => "total_stored_mismatch",
+            IntegrityViolation::OldestStoredMismatch { .. } => "oldest_stored_mismatch",
+            IntegrityViolation::BestPointerMismatch { .. } => "best_pointer_mismatch",
+            IntegrityViolation::FinalizedPointerMismatch { .. } => "finalized_pointer_mismatch",
+            IntegrityViolation::MissingCheckpoint { .. } => "missing_checkpoint",
+            IntegrityViolation::InvalidAuxValue { .. } => "invalid_aux_value",
+            IntegrityViolation::MissingChangeSignallingHeader { .. } => {
+                "missing_change_signalling_header"
+            }
+        }
+    }
+
+    /// Number of the header violation refers to, if any.
+    pub fn number(&self) -> Option<&N> {
+        match self {
+            IntegrityViolation::BrokenParentLink { number, .. }
+            | IntegrityViolation::LookupMismatch { number, .. }
+            | IntegrityViolation::BestPointerMismatch { number, .. }
+            | IntegrityViolation::FinalizedPointerMismatch { number, .. }
+            | IntegrityViolation::MissingCheckpoint { number, .. }
+            | IntegrityViolation::MissingChangeSignallingHeader { number, .. } => Some(number),
+            _ => None,
+        }
+    }
+
+    /// Hash of the header violation refers to, if any.
+    pub fn hash(&self) -> Option<&H> {
+        match self {
+            IntegrityViolation::MissingHeader { hash }
+            | IntegrityViolation::HeaderHashMismatch { hash }
+            | IntegrityViolation::BrokenParentLink { hash, .. }
+            | IntegrityViolation::LookupMismatch { hash, .. }
+            | IntegrityViolation::BestPointerMismatch { hash, .. }
+            | IntegrityViolation::FinalizedPointerMismatch { hash, .. }
+            | IntegrityViolation::MissingCheckpoint { hash, .. }
+            | IntegrityViolation::MissingChangeSignallingHeader { hash, .. } => Some(hash),
+            IntegrityViolation::OldestStoredMismatch { expected, .. } => Some(expected),
+            _ => None,
+        }
+    }
+}
+
+impl<N, H> Display for IntegrityViolation<N, H>
+where
+    N: Display,
+    H: Display,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityViolation::MissingMeta => write!(f, "Storage metadata is missing"),
+            IntegrityViolation::InvalidMeta(s) => {
+                write!(f, "Unable to read storage metadata: {}", s)
+            }
+            IntegrityViolation::MissingHeader { hash } => {
+                write!(f, "Header {} is missing or can not be decoded", hash)
+            }
+            IntegrityViolation::HeaderHashMismatch { hash } => {
+                write!(f, "Header stored under hash {} has different hash", hash)
+            }
+            IntegrityViolation::BrokenParentLink { number, hash } => write!(
+                f,
+                "Header {} at {} does not precede header following it",
+                hash, number
+            ),
+            IntegrityViolation::LookupMismatch { number, hash } => write!(
+                f,
+                "Lookup entry at {} does not point to header {}",
+                number, hash
+            ),
+            IntegrityViolation::TotalStoredMismatch { expected, found } => {
+                write!(f, "Expected {} stored headers, found {}", expected, found)
+            }
+            IntegrityViolation::OldestStoredMismatch { expected, found } => write!(
+                f,
+                "Expected oldest stored header {}, found {}",
+                expected, found
+            ),
+            IntegrityViolation::BestPointerMismatch { number, hash } => write!(
+                f,
+                "Best header {} at {} does not match the stored one",
+                hash, number
+            ),
+            IntegrityViolation::FinalizedPointerMismatch { number, hash } => write!(
+                f,
+                "Finalized header {} at {} is not among stored headers",
+                hash, number
+            ),
+            IntegrityViolation::MissingCheckpoint { number, hash } => {
+                write!(f, "Checkpoint {} at {} is missing", hash, number)
+            }
+            IntegrityViolation::InvalidAuxValue { key, reason } => write!(
+                f,
+                "Invalid value under aux key {}: {}",
+                String::from_utf8_lossy(key),
+                reason
+            ),
+            IntegrityViolation::MissingChangeSignallingHeader { key, number, hash } => write!(
+                f,
+                "Header {} at {} signalling change under aux key {} is missing",
+                hash,
+                number,
+                String::from_utf8_lossy(key)
+            ),
+        }
+    }
+}
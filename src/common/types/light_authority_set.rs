@@ -16,14 +16,15 @@ impl LightAuthoritySet {
         }
     }
 
+    /// Gives authority set following the given one, or `None` if set id would overflow.
     pub fn construct_next_authority_set(
         prev_authority_set: &LightAuthoritySet,
         new_authority_list: AuthorityList,
-    ) -> Self {
-        Self {
-            set_id: prev_authority_set.set_id + 1,
+    ) -> Option<Self> {
+        Some(Self {
+            set_id: prev_authority_set.set_id.checked_add(1)?,
             authorities: new_authority_list,
-        }
+        })
     }
 
     pub fn set_id(&self) -> u64 {
@@ -1,3 +1,6 @@
+pub(crate) mod aura_authority_set;
+pub(crate) mod babe_epoch;
+pub(crate) mod beefy_validator_set;
 pub(crate) mod block_check_params;
 pub(crate) mod block_import_error;
 pub(crate) mod block_import_params;
@@ -15,6 +18,9 @@ pub(crate) mod fork_choice_strategy;
 pub(crate) mod import_result;
 pub(crate) mod imported_aux;
 pub(crate) mod incoming_block;
+pub(crate) mod integrity_violation;
 pub(crate) mod light_authority_set;
 pub(crate) mod new_block_state;
 pub(crate) mod next_change_in_authority;
+pub(crate) mod pruning_policy;
+pub(crate) mod storage_info;
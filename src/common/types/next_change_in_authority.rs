@@ -10,6 +10,9 @@ where
     pub next_change_at: NumberFor<Block>,
     pub block_enacting_this_change: (Block::Hash, NumberFor<Block>),
     pub change: ScheduledChange<NumberFor<Block>>,
+    /// Median last finalized block number signalled along with a forced change.
+    /// It is `None` for standard scheduled changes.
+    pub median_last_finalized: Option<NumberFor<Block>>,
 }
 
 impl<Block> NextChangeInAuthority<Block>
@@ -25,6 +28,25 @@ where
             next_change_at,
             block_enacting_this_change,
             change,
+            median_last_finalized: None,
         }
     }
+
+    pub fn new_forced(
+        next_change_at: NumberFor<Block>,
+        block_enacting_this_change: (Block::Hash, NumberFor<Block>),
+        change: ScheduledChange<NumberFor<Block>>,
+        median_last_finalized: NumberFor<Block>,
+    ) -> Self {
+        Self {
+            next_change_at,
+            block_enacting_this_change,
+            change,
+            median_last_finalized: Some(median_last_finalized),
+        }
+    }
+
+    pub fn is_forced(&self) -> bool {
+        self.median_last_finalized.is_some()
+    }
 }
@@ -0,0 +1,11 @@
+use parity_scale_codec::{Decode, Encode};
+
+/// Decides which headers are retained as checkpoints once they
+/// fall out of the range of most recent headers kept in storage.
+#[derive(Debug, Encode, Decode, Clone, Default, PartialEq)]
+pub struct PruningPolicy {
+    /// Retain headers signalling authority set change.
+    pub checkpoint_authority_set_changes: bool,
+    /// Retain headers whose number is a multiple of it. Zero disables it.
+    pub checkpoint_interval: u64,
+}
@@ -0,0 +1,12 @@
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+/// Storage usage info
+#[derive(Debug)]
+pub struct StorageInfo<Block: BlockT> {
+    /// Number of headers stored at the moment.
+    pub total_stored: u64,
+    /// Hash of the oldest stored header.
+    pub oldest_stored_hash: Block::Hash,
+    /// Number and hash of headers retained after being pruned.
+    pub checkpoints: Vec<(NumberFor<Block>, Block::Hash)>,
+}
@@ -1,33 +1,62 @@
 use crate::common::traits::aux_store::AuxStore;
+use crate::common::types::aura_authority_set::AuraAuthoritySet;
+use crate::common::types::babe_epoch::BabeEpoch;
+use crate::common::types::beefy_validator_set::{
+    BeefyMmrRoot, BeefyNextAuthoritySet, BeefyValidatorSet,
+};
 use crate::common::types::blockchain_error::BlockchainError;
 use crate::common::types::light_authority_set::LightAuthoritySet;
 use crate::common::types::next_change_in_authority::NextChangeInAuthority;
-use crate::db;
 use crate::storage::Storage;
+use kvdb::KeyValueDB;
 use parity_scale_codec::alloc::sync::Arc;
 use parity_scale_codec::{Decode, Encode};
-use sp_runtime::traits::Block as BlockT;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
 
 // Purposely shorthanded name just to save few bytes of storage
 pub const NEXT_CHANGE_IN_AUTHORITY_KEY: &'static [u8] = b"nca";
+pub const NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY: &'static [u8] = b"nfca";
 pub static GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY: &[u8] = b"grandpa_aci";
+pub static GRANDPA_FORCED_AUTHORITY_CHANGE_INTERMEDIATE_KEY: &[u8] = b"grandpa_faci";
+pub static BABE_EPOCH_CHANGES_INTERMEDIATE_KEY: &[u8] = b"babe_eci";
+pub static AURA_AUTHORITIES_CHANGE_INTERMEDIATE_KEY: &[u8] = b"aura_aci";
+
+/// BABE epoch in effect and the one announced to follow it are saved under these keys in aux storage.
+pub const BABE_EPOCH_KEY: &[u8] = b"babe_epoch";
+pub const BABE_NEXT_EPOCH_KEY: &[u8] = b"babe_next_epoch";
+
+/// AuraAuthoritySet is saved under this key in aux storage.
+pub const AURA_AUTHORITY_SET_KEY: &[u8] = b"aura_authorities";
+
+/// BEEFY validator set, the next one announced by MMR leaves and the latest
+/// MMR root signed by validators are saved under these keys in aux storage.
+pub const BEEFY_VALIDATOR_SET_KEY: &[u8] = b"beefy_validators";
+pub const BEEFY_NEXT_AUTHORITY_SET_KEY: &[u8] = b"beefy_next_authorities";
+pub const BEEFY_MMR_ROOT_KEY: &[u8] = b"beefy_mmr_root";
 
 /// LightAuthoritySet is saved under this key in aux storage.
 pub const LIGHT_AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 
+/// PruningPolicy is saved under this key in aux storage.
+pub const PRUNING_POLICY_KEY: &[u8] = b"pruning_policy";
+/// Every header retained as checkpoint is saved in aux storage under this prefix followed
+/// by its number, linked to the preceding one, while number of the latest one is saved
+/// under the other key. Checkpoints are kept apart, so that retaining one costs the same
+/// regardless of how many are retained already.
+pub const CHECKPOINT_PREFIX: &[u8] = b"checkpoint";
+pub const LATEST_CHECKPOINT_KEY: &[u8] = b"latest_checkpoint";
+
 // Columns supported in our in memory db
 pub const NUM_COLUMNS: u32 = 11;
 
-pub fn initialize_storage(
-    encoded_data: Vec<u8>,
+pub fn initialize_storage<D>(
+    db: D,
     max_headers_allowed_to_store: u64,
-) -> Result<(db::Data, Arc<Storage>), BlockchainError> {
-    let data = db::Data::decode(&mut encoded_data.as_slice()).unwrap();
-
-    return Ok((
-        data.clone(),
-        Arc::new(Storage::new(data, max_headers_allowed_to_store)?),
-    ));
+) -> Result<Arc<Storage<D>>, BlockchainError>
+where
+    D: KeyValueDB,
+{
+    Ok(Arc::new(Storage::new(db, max_headers_allowed_to_store)?))
 }
 
 pub fn store_next_authority_change<AS, Block>(
@@ -38,13 +67,13 @@ where
     AS: AuxStore,
     Block: BlockT,
 {
-    aux_store.insert_aux(
-        &[(
-            NEXT_CHANGE_IN_AUTHORITY_KEY,
-            next_authority_change.encode().as_slice(),
-        )],
-        &[],
-    )
+    // Forced changes are tracked separately, as both kind of changes can be pending at once.
+    let key = if next_authority_change.is_forced() {
+        NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY
+    } else {
+        NEXT_CHANGE_IN_AUTHORITY_KEY
+    };
+    aux_store.insert_aux(&[(key, next_authority_change.encode().as_slice())], &[])
 }
 
 pub fn delete_next_authority_change<AS>(aux_store: Arc<AS>) -> Result<(), BlockchainError>
@@ -54,6 +83,13 @@ where
     aux_store.insert_aux(&[], &[NEXT_CHANGE_IN_AUTHORITY_KEY])
 }
 
+pub fn delete_next_forced_authority_change<AS>(aux_store: Arc<AS>) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[], &[NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY])
+}
+
 pub fn fetch_next_authority_change<AS, Block>(
     aux_store: Arc<AS>,
 ) -> Result<Option<NextChangeInAuthority<Block>>, BlockchainError>
@@ -61,7 +97,28 @@ where
     AS: AuxStore,
     Block: BlockT,
 {
-    let encoded_next_possible_authority_change = aux_store.get_aux(NEXT_CHANGE_IN_AUTHORITY_KEY)?;
+    fetch_authority_change(aux_store.as_ref(), NEXT_CHANGE_IN_AUTHORITY_KEY)
+}
+
+pub fn fetch_next_forced_authority_change<AS, Block>(
+    aux_store: Arc<AS>,
+) -> Result<Option<NextChangeInAuthority<Block>>, BlockchainError>
+where
+    AS: AuxStore,
+    Block: BlockT,
+{
+    fetch_authority_change(aux_store.as_ref(), NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY)
+}
+
+pub(crate) fn fetch_authority_change<AS, Block>(
+    aux_store: &AS,
+    key: &[u8],
+) -> Result<Option<NextChangeInAuthority<Block>>, BlockchainError>
+where
+    AS: AuxStore,
+    Block: BlockT,
+{
+    let encoded_next_possible_authority_change = aux_store.get_aux(key)?;
 
     if encoded_next_possible_authority_change.is_none() {
         return Ok(None);
@@ -120,3 +177,232 @@ where
 
     Ok(Some(light_authority_set))
 }
+
+pub fn insert_babe_epoch<AS>(aux_store: Arc<AS>, epoch: &BabeEpoch) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[(BABE_EPOCH_KEY, epoch.encode().as_slice())], &[])
+}
+
+pub fn insert_next_babe_epoch<AS>(
+    aux_store: Arc<AS>,
+    epoch: &BabeEpoch,
+) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[(BABE_NEXT_EPOCH_KEY, epoch.encode().as_slice())], &[])
+}
+
+/// Removes BABE epochs, which disables verification of BABE seals.
+pub fn delete_babe_epochs<AS>(aux_store: Arc<AS>) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[], &[BABE_EPOCH_KEY, BABE_NEXT_EPOCH_KEY])
+}
+
+pub fn delete_next_babe_epoch<AS>(aux_store: Arc<AS>) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[], &[BABE_NEXT_EPOCH_KEY])
+}
+
+pub fn fetch_babe_epoch<AS>(aux_store: Arc<AS>) -> Result<Option<BabeEpoch>, BlockchainError>
+where
+    AS: AuxStore,
+{
+    fetch_epoch(aux_store.as_ref(), BABE_EPOCH_KEY)
+}
+
+pub fn fetch_next_babe_epoch<AS>(aux_store: Arc<AS>) -> Result<Option<BabeEpoch>, BlockchainError>
+where
+    AS: AuxStore,
+{
+    fetch_epoch(aux_store.as_ref(), BABE_NEXT_EPOCH_KEY)
+}
+
+fn fetch_epoch<AS>(aux_store: &AS, key: &[u8]) -> Result<Option<BabeEpoch>, BlockchainError>
+where
+    AS: AuxStore,
+{
+    let encoded_possible_epoch = aux_store.get_aux(key)?;
+
+    if encoded_possible_epoch.is_none() {
+        return Ok(None);
+    }
+
+    let encoded_epoch = encoded_possible_epoch.unwrap();
+
+    let epoch = BabeEpoch::decode(&mut encoded_epoch.as_slice()).map_err(|err| {
+        BlockchainError::Backend(format!(
+            "Unable to decode BABE epoch. DB might be corrupted. Underlying Error: {}",
+            err.what()
+        ))
+    })?;
+
+    Ok(Some(epoch))
+}
+
+pub fn insert_aura_authority_set<AS>(
+    aux_store: Arc<AS>,
+    aura_authority_set: &AuraAuthoritySet,
+) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(
+        &[(
+            AURA_AUTHORITY_SET_KEY,
+            aura_authority_set.encode().as_slice(),
+        )],
+        &[],
+    )
+}
+
+/// Removes Aura authority set, which disables verification of Aura seals.
+pub fn delete_aura_authority_set<AS>(aux_store: Arc<AS>) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[], &[AURA_AUTHORITY_SET_KEY])
+}
+
+pub fn fetch_aura_authority_set<AS>(
+    aux_store: Arc<AS>,
+) -> Result<Option<AuraAuthoritySet>, BlockchainError>
+where
+    AS: AuxStore,
+{
+    let encoded_possible_aura_authority_set = aux_store.get_aux(AURA_AUTHORITY_SET_KEY)?;
+
+    if encoded_possible_aura_authority_set.is_none() {
+        return Ok(None);
+    }
+
+    let encoded_aura_authority_set = encoded_possible_aura_authority_set.unwrap();
+
+    let aura_authority_set = AuraAuthoritySet::decode(&mut encoded_aura_authority_set.as_slice())
+        .map_err(|err| {
+        BlockchainError::Backend(format!(
+            "Unable to decode Aura authority set. DB might be corrupted. Underlying Error: {}",
+            err.what()
+        ))
+    })?;
+
+    Ok(Some(aura_authority_set))
+}
+
+pub fn insert_beefy_validator_set<AS>(
+    aux_store: Arc<AS>,
+    validator_set: &BeefyValidatorSet,
+) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(
+        &[(BEEFY_VALIDATOR_SET_KEY, validator_set.encode().as_slice())],
+        &[],
+    )
+}
+
+pub fn insert_beefy_next_authority_set<AS>(
+    aux_store: Arc<AS>,
+    next_authority_set: &BeefyNextAuthoritySet,
+) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(
+        &[(
+            BEEFY_NEXT_AUTHORITY_SET_KEY,
+            next_authority_set.encode().as_slice(),
+        )],
+        &[],
+    )
+}
+
+pub fn insert_beefy_mmr_root<AS, Block>(
+    aux_store: Arc<AS>,
+    mmr_root: &BeefyMmrRoot<NumberFor<Block>>,
+) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+    Block: BlockT,
+{
+    aux_store.insert_aux(&[(BEEFY_MMR_ROOT_KEY, mmr_root.encode().as_slice())], &[])
+}
+
+/// Removes what was learned from BEEFY commitments, leaving validator set in place.
+pub fn delete_beefy_commitment_data<AS>(aux_store: Arc<AS>) -> Result<(), BlockchainError>
+where
+    AS: AuxStore,
+{
+    aux_store.insert_aux(&[], &[BEEFY_NEXT_AUTHORITY_SET_KEY, BEEFY_MMR_ROOT_KEY])
+}
+
+pub fn fetch_beefy_validator_set<AS>(
+    aux_store: Arc<AS>,
+) -> Result<Option<BeefyValidatorSet>, BlockchainError>
+where
+    AS: AuxStore,
+{
+    fetch_beefy_data(
+        aux_store.as_ref(),
+        BEEFY_VALIDATOR_SET_KEY,
+        "BEEFY validator set",
+    )
+}
+
+pub fn fetch_beefy_next_authority_set<AS>(
+    aux_store: Arc<AS>,
+) -> Result<Option<BeefyNextAuthoritySet>, BlockchainError>
+where
+    AS: AuxStore,
+{
+    fetch_beefy_data(
+        aux_store.as_ref(),
+        BEEFY_NEXT_AUTHORITY_SET_KEY,
+        "next BEEFY authority set",
+    )
+}
+
+pub fn fetch_beefy_mmr_root<AS, Block>(
+    aux_store: Arc<AS>,
+) -> Result<Option<BeefyMmrRoot<NumberFor<Block>>>, BlockchainError>
+where
+    AS: AuxStore,
+    Block: BlockT,
+{
+    fetch_beefy_data(aux_store.as_ref(), BEEFY_MMR_ROOT_KEY, "BEEFY MMR root")
+}
+
+fn fetch_beefy_data<AS, T>(
+    aux_store: &AS,
+    key: &[u8],
+    name: &str,
+) -> Result<Option<T>, BlockchainError>
+where
+    AS: AuxStore,
+    T: Decode,
+{
+    let encoded_possible_data = aux_store.get_aux(key)?;
+
+    if encoded_possible_data.is_none() {
+        return Ok(None);
+    }
+
+    let encoded_data = encoded_possible_data.unwrap();
+
+    let data = T::decode(&mut encoded_data.as_slice()).map_err(|err| {
+        BlockchainError::Backend(format!(
+            "Unable to decode {}. DB might be corrupted. Underlying Error: {}",
+            name,
+            err.what()
+        ))
+    })?;
+
+    Ok(Some(data))
+}
@@ -0,0 +1,403 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{Binary, Env, Extern};
+use parity_scale_codec::{Decode, Encode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sp_finality_grandpa::{AuthorityList, ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
+use sp_runtime::traits::Header as HeaderT;
+use sp_runtime::DigestItem;
+
+use crate::contract::msg::CheckpointPolicy;
+use crate::contract::state::{HeaderFormat, Misbehaviour, TimestampCallIndex};
+use crate::contract::{contract_state, handle, init};
+use crate::msg::{HandleMsg, HeaderInitMsg, InitMsg};
+use crate::types::{Header, SignedBlock};
+
+/// Kusama block 55439, which client is instantiated with.
+const INIT_BLOCK: &str = "0x5e9fc49076803d0ba88c719252ede5ae713d09367162d344e9b79ef3aac2efa03e620300fe518cc595e8f5ede8010cf6d26352f6a089ee52f992153a540c7b5d9b659ea272c9c1e535cf5ca49ab2d72059671d80f69c6dba7e6c0dca1e27c3832e873f2b08066175726120448dd10f0000000005617572610101fe734978fa3cb9804346988424124add53316e68e9dcd96a5dfc5a576fe61262031463e0e3a1cdb15538a763dddfbbdf2d3c47e3ecc72deebb3ba5ec59b1168204280402000bc0e95ebf720100";
+
+/// Kusama block 55440, child of `INIT_BLOCK`.
+const UPDATE_BLOCK: &str = "0xf157283bcfe5ace5f3258bdb595ee8c6761394a56c8e73b6aaf734e6fb1e7c92426203000ad92ba15285e38e29472d35c29a8e0097e0748fa66fca1b4c834e13f0604de6f7e776ac0632a86d967e1fc4694d51b15c06dadf6c2d0f60a0c661993ffa6d5308066175726120458dd10f00000000056175726101019c9a0a6afd95ff9b8a479bab6676867d19f388b187534394661f0b9ca540b86cd5847174d8b1075f61c01f3b0f5dfa8c643b15c226ebace6aa5aca43cd12ce8504280402000b30015fbf720100";
+
+/// Kusama authority set in effect at `INIT_BLOCK`.
+const AUTHORITY_SET: &str =
+    "0x0488dc3417d5058ec4b4503e0c12ea1a0a89be200fe98922423d4334014fa6b0ee0100000000000000";
+
+/// Number of random inputs fed to each entry point, which can
+/// be raised through `FUZZ_ITERATIONS` environment variable.
+const DEFAULT_ITERATIONS: u64 = 200;
+
+fn iterations() -> u64 {
+    std::env::var("FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_ITERATIONS)
+}
+
+fn new_extern() -> Extern<MockStorage, MockApi, MockQuerier> {
+    Extern {
+        storage: MockStorage::new(),
+        api: MockApi::new(5),
+        querier: MockQuerier::default(),
+    }
+}
+
+fn init_msg(block: String, set_id: u64, authority_set: String) -> InitMsg {
+    InitMsg::Header(HeaderInitMsg {
+        name: "fuzz".into(),
+        block,
+        authority_set,
+        max_headers_allowed_to_store: 256,
+        set_id,
+        max_headers_allowed_between_justifications: 16,
+        trusting_period: 0,
+        header_format: HeaderFormat::default(),
+        checkpoints: CheckpointPolicy::default(),
+        babe: None,
+        aura: None,
+        beefy: None,
+        timestamp_call_index: TimestampCallIndex::default(),
+    })
+}
+
+/// Gives extern with client instantiated from `INIT_BLOCK`.
+fn initialized_extern() -> Extern<MockStorage, MockApi, MockQuerier> {
+    let mut deps = new_extern();
+    let msg = init_msg(INIT_BLOCK.into(), 1, AUTHORITY_SET.into());
+    assert!(init(&mut deps, Env::default(), msg).is_ok());
+    deps
+}
+
+/// Runs given closure, failing with offending input if it panics.
+fn assert_no_panic<R, F: FnOnce() -> R>(input: &str, f: F) -> R {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(_) => panic!("Contract panicked on input: {}", input),
+    }
+}
+
+fn decode_hex(value: &str) -> Vec<u8> {
+    hex::decode(value.trim_start_matches("0x")).expect("Fixture need to be valid hex")
+}
+
+fn encode_hex<T: Encode>(value: &T) -> String {
+    format!("0x{}", hex::encode(value.encode()))
+}
+
+fn signed_block(value: &str) -> SignedBlock {
+    SignedBlock::decode(&mut decode_hex(value).as_slice()).expect("Fixture need to be valid block")
+}
+
+fn authorities() -> AuthorityList {
+    AuthorityList::decode(&mut decode_hex(AUTHORITY_SET).as_slice())
+        .expect("Fixture need to be valid authority list")
+}
+
+fn random_bytes(rng: &mut StdRng, max_len: usize) -> Vec<u8> {
+    let len = rng.gen_range(0, max_len + 1);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+/// Corrupts valid input by flipping bits, truncating, inserting or
+/// overwriting bytes, or replaces it with random bytes altogether.
+fn mutate(rng: &mut StdRng, bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    match rng.gen_range(0, 5) {
+        0 if !bytes.is_empty() => {
+            for _ in 0..rng.gen_range(1, 8) {
+                let index = rng.gen_range(0, bytes.len());
+                bytes[index] ^= 1u8 << rng.gen_range(0u32, 8);
+            }
+        }
+        1 => {
+            let len = rng.gen_range(0, bytes.len() + 1);
+            bytes.truncate(len);
+        }
+        2 => {
+            let index = rng.gen_range(0, bytes.len() + 1);
+            let inserted = random_bytes(rng, 64);
+            bytes.splice(index..index, inserted);
+        }
+        3 if !bytes.is_empty() => {
+            let start = rng.gen_range(0, bytes.len());
+            let end = rng.gen_range(start, bytes.len() + 1);
+            for byte in &mut bytes[start..end] {
+                *byte = rng.gen();
+            }
+        }
+        _ => bytes = random_bytes(rng, 256),
+    }
+    bytes
+}
+
+/// Encodes bytes the way contract expects them, or in a way it doesn't.
+fn encode_mutated(rng: &mut StdRng, bytes: &[u8]) -> String {
+    match rng.gen_range(0, 6) {
+        0 | 1 => format!("0x{}", hex::encode(bytes)),
+        2 => hex::encode(bytes),
+        3 => {
+            // Odd number of hex digits
+            let encoded = hex::encode(bytes);
+            format!("0x{}", &encoded[..encoded.len().saturating_sub(1)])
+        }
+        4 => ["", "0", "0x", "0x0", "=", "0xzz", "\u{1F980}"][rng.gen_range(0, 7)].to_string(),
+        _ => random_bytes(rng, 32)
+            .into_iter()
+            .map(|byte| char::from(byte % 128))
+            .collect(),
+    }
+}
+
+fn mutated(rng: &mut StdRng, bytes: &[u8]) -> String {
+    let bytes = mutate(rng, bytes);
+    encode_mutated(rng, &bytes)
+}
+
+fn random_handle_msg(rng: &mut StdRng) -> HandleMsg {
+    let block = decode_hex(UPDATE_BLOCK);
+    let header = signed_block(UPDATE_BLOCK).block.header.encode();
+    let authority_set = decode_hex(AUTHORITY_SET);
+    match rng.gen_range(0, 9) {
+        0 => HandleMsg::UpdateClient {
+            block: mutated(rng, &block),
+            authority_set: if rng.gen() {
+                String::new()
+            } else {
+                mutated(rng, &authority_set)
+            },
+        },
+        1 => HandleMsg::UpdateClientHeader {
+            header: Binary(mutate(rng, &header)),
+            justification: if rng.gen() {
+                None
+            } else {
+                let justification = random_bytes(rng, 128);
+                Some(Binary(mutate(rng, &justification)))
+            },
+            timestamp_proof: None,
+        },
+        2 => {
+            let mut blocks = vec![];
+            if rng.gen() {
+                blocks.push(UPDATE_BLOCK.to_string());
+            }
+            for _ in 0..rng.gen_range(0, 4) {
+                blocks.push(mutated(rng, &block));
+            }
+            HandleMsg::UpdateClientBatch { blocks }
+        }
+        3 => HandleMsg::UpdateClientSkipAhead {
+            block: mutated(rng, &block),
+        },
+        4 => {
+            let justification_a = random_bytes(rng, 128);
+            let justification_b = random_bytes(rng, 128);
+            HandleMsg::SubmitMisbehaviour {
+                header_a: mutated(rng, &header),
+                justification_a: mutated(rng, &justification_a),
+                header_b: mutated(rng, &header),
+                justification_b: mutated(rng, &justification_b),
+            }
+        }
+        5 => {
+            let justification = random_bytes(rng, 128);
+            HandleMsg::SubmitJustification {
+                justification: mutated(rng, &justification),
+                timestamp_proof: None,
+            }
+        }
+        6 => {
+            let finality_proof = random_bytes(rng, 256);
+            HandleMsg::SubmitBeefyFinalityProof {
+                finality_proof: mutated(rng, &finality_proof),
+                next_validators: None,
+                timestamp_proof: None,
+            }
+        }
+        7 => {
+            let warp_sync_proof = random_bytes(rng, 256);
+            HandleMsg::UpdateClientWarpSync {
+                warp_sync_proof: mutated(rng, &warp_sync_proof),
+                timestamp_proof: None,
+            }
+        }
+        _ => HandleMsg::RecoverClient {
+            block: mutated(rng, &block),
+            set_id: rng.gen(),
+            authority_set: mutated(rng, &authority_set),
+            babe: None,
+            aura: None,
+            beefy: None,
+        },
+    }
+}
+
+#[test]
+fn fuzz_init() {
+    let mut rng = StdRng::seed_from_u64(19);
+    let block = decode_hex(INIT_BLOCK);
+    let authority_set = decode_hex(AUTHORITY_SET);
+    for _ in 0..iterations() {
+        let block = if rng.gen_range(0, 4) == 0 {
+            INIT_BLOCK.to_string()
+        } else {
+            mutated(&mut rng, &block)
+        };
+        let authority_set = if rng.gen() {
+            AUTHORITY_SET.to_string()
+        } else {
+            mutated(&mut rng, &authority_set)
+        };
+        let set_id = if rng.gen() { 1 } else { rng.gen() };
+        let input = format!(
+            "block: {}, set_id: {}, authority_set: {}",
+            block, set_id, authority_set
+        );
+        let msg = init_msg(block, set_id, authority_set);
+        assert_no_panic(&input, || init(&mut new_extern(), Env::default(), msg));
+    }
+}
+
+#[test]
+fn fuzz_handle() {
+    let mut rng = StdRng::seed_from_u64(19);
+    for _ in 0..iterations() {
+        let msg = random_handle_msg(&mut rng);
+        let input = serde_json::to_string(&msg).expect("Handle message need to be serializable");
+        let mut deps = initialized_extern();
+        if let HandleMsg::RecoverClient { .. } = msg {
+            // Only frozen client can be recovered
+            assert!(contract_state(&mut deps.storage)
+                .update(|mut state| {
+                    state.misbehaviour = Some(Misbehaviour {
+                        header_a: String::new(),
+                        justification_a: String::new(),
+                        header_b: String::new(),
+                        justification_b: String::new(),
+                    });
+                    Ok(state)
+                })
+                .is_ok());
+        }
+        assert_no_panic(&input, || handle(&mut deps, Env::default(), msg));
+    }
+}
+
+#[test]
+fn init_rejects_authority_set_without_voters() {
+    let no_authorities: Vec<u8> = vec![0];
+    let mut zero_weight_authorities = decode_hex(AUTHORITY_SET);
+    let len = zero_weight_authorities.len();
+    zero_weight_authorities[len - 8..].copy_from_slice(&[0; 8]);
+
+    for authority_set in &[no_authorities, zero_weight_authorities] {
+        let authority_set = format!("0x{}", hex::encode(authority_set));
+        let msg = init_msg(INIT_BLOCK.into(), 1, authority_set.clone());
+        let result = assert_no_panic(&authority_set, || {
+            init(&mut new_extern(), Env::default(), msg)
+        });
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn update_rejects_scheduled_change_overflowing_block_number() {
+    let mut deps = initialized_extern();
+    let mut block = signed_block(UPDATE_BLOCK);
+    block.block.header.digest.push(DigestItem::Consensus(
+        GRANDPA_ENGINE_ID,
+        ConsensusLog::<u32>::ScheduledChange(ScheduledChange {
+            next_authorities: authorities(),
+            delay: u32::max_value(),
+        })
+        .encode(),
+    ));
+    let msg = HandleMsg::UpdateClient {
+        block: encode_hex(&block),
+        authority_set: String::new(),
+    };
+    let result = assert_no_panic("scheduled change with maximum delay", || {
+        handle(&mut deps, Env::default(), msg)
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn update_rejects_change_overflowing_set_id() {
+    let mut deps = new_extern();
+    let msg = init_msg(INIT_BLOCK.into(), u64::max_value(), AUTHORITY_SET.into());
+    assert!(init(&mut deps, Env::default(), msg).is_ok());
+
+    // Forced change signalled by the next header is enacted by the one following it
+    let mut header = signed_block(UPDATE_BLOCK).block.header;
+    header.digest.push(DigestItem::Consensus(
+        GRANDPA_ENGINE_ID,
+        ConsensusLog::<u32>::ForcedChange(
+            0,
+            ScheduledChange {
+                next_authorities: authorities(),
+                delay: 1,
+            },
+        )
+        .encode(),
+    ));
+    let next_header = Header::new(
+        header.number + 1,
+        Default::default(),
+        Default::default(),
+        header.hash(),
+        Default::default(),
+    );
+
+    let msg = HandleMsg::UpdateClientHeader {
+        header: Binary(header.encode()),
+        justification: None,
+        timestamp_proof: None,
+    };
+    assert!(handle(&mut deps, Env::default(), msg).is_ok());
+    let msg = HandleMsg::UpdateClientHeader {
+        header: Binary(next_header.encode()),
+        justification: None,
+        timestamp_proof: None,
+    };
+    let result = assert_no_panic("change enacted after maximum set id", || {
+        handle(&mut deps, Env::default(), msg)
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn update_rejects_change_signalled_at_maximum_block_number() {
+    let mut init_block = signed_block(INIT_BLOCK);
+    init_block.block.header.number = u32::max_value();
+    let mut deps = new_extern();
+    let msg = init_msg(encode_hex(&init_block), 1, AUTHORITY_SET.into());
+    assert!(init(&mut deps, Env::default(), msg).is_ok());
+
+    let mut header = Header::new(
+        u32::max_value(),
+        Default::default(),
+        Default::default(),
+        init_block.block.header.hash(),
+        Default::default(),
+    );
+    header.digest.push(DigestItem::Consensus(
+        GRANDPA_ENGINE_ID,
+        ConsensusLog::<u32>::ScheduledChange(ScheduledChange {
+            next_authorities: authorities(),
+            delay: 1,
+        })
+        .encode(),
+    ));
+    let msg = HandleMsg::UpdateClientHeader {
+        header: Binary(header.encode()),
+        justification: None,
+        timestamp_proof: None,
+    };
+    let result = assert_no_panic("header at maximum block number", || {
+        handle(&mut deps, Env::default(), msg)
+    });
+    assert!(result.is_err());
+}
@@ -1,32 +1,255 @@
+use cosmwasm_std::{Binary, HumanAddr};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::contract::state::H256;
-use crate::types::BlockNumber;
+use crate::contract::state::{BlockNumber, HeaderFormat, Status, TimestampCallIndex, H256};
 
+/// Client is instantiated either from a trusted header and authority
+/// set, or from a snapshot exported by another client. Message is tagged
+/// by its kind, as in `{"header": {..}}`, because untagged enums can't be
+/// parsed by the JSON deserializer contracts run with.
 #[derive(Serialize, Deserialize, JsonSchema)]
-pub struct InitMsg {
+#[serde(rename_all = "lowercase")]
+pub enum InitMsg {
+    Header(HeaderInitMsg),
+    Snapshot(SnapshotInitMsg),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct HeaderInitMsg {
     pub name: String,
     pub block: String,
     pub set_id: u64,
     pub authority_set: String,
     pub max_headers_allowed_to_store: u64,
     pub max_headers_allowed_between_justifications: u64,
+    /// Period in seconds after which client expires, unless a newer
+    /// header is finalized. Zero means client never expires.
+    #[serde(default)]
+    pub trusting_period: u64,
+    /// Block number type and hasher used by the chain. Defaults
+    /// to `u32` block numbers and Blake2-256 hashing.
+    #[serde(default)]
+    pub header_format: HeaderFormat,
+    /// Headers retained after they are pruned. By default,
+    /// only the most recent headers are kept.
+    #[serde(default)]
+    pub checkpoints: CheckpointPolicy,
+    /// BABE epochs, which headers ingested without justification are verified
+    /// against. BABE seals are not verified if it is omitted.
+    #[serde(default)]
+    pub babe: Option<BabeInitMsg>,
+    /// Aura authorities, which headers ingested without justification are verified
+    /// against. Aura seals are not verified if it is omitted. Can not be combined with `babe`.
+    #[serde(default)]
+    pub aura: Option<AuraInitMsg>,
+    /// BEEFY validator set in effect at the trusted header. Headers ingested without
+    /// justification can only be finalized by BEEFY finality proofs once it is supplied.
+    #[serde(default)]
+    pub beefy: Option<BeefyInitMsg>,
+    /// Pallet and call index of `timestamp::set`, which timestamp inherent of a block is
+    /// recognized by. Defaults to `[2, 0]`, as in Polkadot, Kusama and Westend runtimes.
+    #[serde(default)]
+    pub timestamp_call_index: TimestampCallIndex,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotInitMsg {
+    /// Snapshot returned by `ExportSnapshot` query. Client takes its name
+    /// and configuration, except for admin, which is the instantiator.
+    pub snapshot: String,
+}
+
+/// Sent once contract code is replaced, to upgrade persisted state.
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
+pub struct MigrateMsg {
+    /// Replaces admin of the client. Clients instantiated before admin was
+    /// recorded get the sender of this message, if it is not provided.
+    #[serde(default)]
+    pub admin: Option<HumanAddr>,
+}
+
+/// Decides which headers are retained as checkpoints once they fall out of
+/// the range of `max_headers_allowed_to_store` most recent headers.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct CheckpointPolicy {
+    /// Retain headers signalling authority set change.
+    #[serde(default)]
+    pub authority_set_changes: bool,
+    /// Retain headers whose number is a multiple of it. Zero disables it.
+    #[serde(default)]
+    pub interval: u64,
+}
+
+/// BABE epoch in effect at the trusted header, along with the one following it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BabeInitMsg {
+    pub epoch_index: u64,
+    pub start_slot: u64,
+    /// Number of slots in each epoch.
+    pub epoch_duration: u64,
+    /// Probability of a slot being empty, as numerator and denominator.
+    pub c: (u64, u64),
+    pub secondary_slots: bool,
+    pub epoch: BabeEpochMsg,
+    /// Epoch announced by the first header of the current epoch. Without it, headers
+    /// of the next epoch need justification until an epoch is announced again.
+    #[serde(default)]
+    pub next_epoch: Option<BabeEpochMsg>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BabeEpochMsg {
+    /// Hex encoded SCALE encoded list of sr25519 authorities along with their weights.
+    pub authorities: String,
+    /// Hex encoded epoch randomness.
+    pub randomness: String,
+}
+
+/// Aura authorities in effect after the trusted header.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuraInitMsg {
+    pub crypto: AuraCryptoType,
+    /// Hex encoded SCALE encoded list of authority public keys.
+    pub authorities: String,
+}
+
+/// BEEFY validator set in effect at the trusted header.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BeefyInitMsg {
+    pub validator_set_id: u64,
+    /// Hex encoded SCALE encoded list of compressed secp256k1 public keys.
+    pub validators: String,
+}
+
+/// Signature scheme of Aura authorities.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuraCryptoType {
+    Sr25519,
+    Ed25519,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum HandleMsg {
     UpdateClient {
         block: String,
+        /// Authority set expected to be in effect after ingesting the block.
+        /// If supplied, update is rejected on mismatch.
+        #[serde(default)]
         authority_set: String,
     },
+    /// Same as `UpdateClient`, but takes SCALE encoded header and
+    /// optional GRANDPA justification instead of the whole block,
+    /// both given as base64 `Binary`.
+    UpdateClientHeader {
+        header: Binary,
+        #[serde(default)]
+        justification: Option<Binary>,
+        /// Storage proof of `Timestamp::Now` against state root of the header, which
+        /// advances timestamp of the last finalized header. Requires justification.
+        #[serde(default)]
+        timestamp_proof: Option<Vec<String>>,
+    },
+    UpdateClientBatch {
+        blocks: Vec<String>,
+    },
+    UpdateClientSkipAhead {
+        block: String,
+    },
+    /// Skips ahead by SCALE encoded GRANDPA warp sync proof, moving authority set forward by
+    /// the changes its fragments schedule, and ingests header of the last fragment.
+    UpdateClientWarpSync {
+        warp_sync_proof: String,
+        /// Storage proof of `Timestamp::Now` against state root of the last fragment
+        /// header, which advances timestamp of the last finalized header.
+        #[serde(default)]
+        timestamp_proof: Option<Vec<String>>,
+    },
+    /// Finalizes header ingested earlier without justification, along with
+    /// its ancestors. Justification need to be signed by the current authority set.
+    SubmitJustification {
+        justification: String,
+        /// Storage proof of `Timestamp::Now` against state root of the finalized
+        /// header, which advances timestamp of the last finalized header.
+        #[serde(default)]
+        timestamp_proof: Option<Vec<String>>,
+    },
+    /// Finalizes header ingested earlier, along with its ancestors, by SCALE encoded BEEFY
+    /// signed commitment, MMR leaf committing to the header and proof of the leaf in the MMR.
+    /// Validators of the next set are required once commitments are signed by it.
+    SubmitBeefyFinalityProof {
+        finality_proof: String,
+        #[serde(default)]
+        next_validators: Option<String>,
+        /// Storage proof of `Timestamp::Now` against state root of the finalized
+        /// header, which advances timestamp of the last finalized header.
+        #[serde(default)]
+        timestamp_proof: Option<Vec<String>>,
+    },
+    SubmitMisbehaviour {
+        header_a: String,
+        justification_a: String,
+        header_b: String,
+        justification_b: String,
+    },
+    RecoverClient {
+        block: String,
+        set_id: u64,
+        authority_set: String,
+        /// Replaces BABE epochs. Either it or `aura` is required if client verifies block seals.
+        #[serde(default)]
+        babe: Option<BabeInitMsg>,
+        /// Replaces Aura authority set. Either it or `babe` is required if client verifies block seals.
+        #[serde(default)]
+        aura: Option<AuraInitMsg>,
+        /// Replaces BEEFY validator set. Required if client tracks BEEFY.
+        #[serde(default)]
+        beefy: Option<BeefyInitMsg>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum QueryMsg {
     LatestHeight {},
+    VerifyMembership {
+        height: BlockNumber,
+        key: String,
+        value: String,
+        proof: Vec<String>,
+    },
+    VerifyNonMembership {
+        height: BlockNumber,
+        key: String,
+        proof: Vec<String>,
+    },
+    HeaderByNumber {
+        number: BlockNumber,
+    },
+    HeaderByHash {
+        /// Hex encoded header hash.
+        hash: String,
+    },
+    FinalizedHeader {},
+    /// Stored headers with numbers between `from` and `to` (inclusive),
+    /// in ascending order. At most `limit` headers are returned.
+    Headers {
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: Option<u32>,
+    },
+    StorageInfo {},
+    Status {
+        /// Time in seconds against which expiry is checked,
+        /// normally time of the latest block.
+        time: u64,
+    },
+    AuthoritySet {},
+    BeefyState {},
+    CheckIntegrity {},
+    ExportSnapshot {},
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -35,5 +258,136 @@ pub struct LatestHeightResponse {
     pub best_header_hash: H256,
     pub last_finalized_header_hash: H256,
     pub best_header_commitment_root: H256,
-    pub current_authority_set: String,
+    pub current_authority_set: AuthoritySet,
+    pub next_authority_change: Option<AuthorityChange>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Authority {
+    /// Hex encoded ed25519 public key.
+    pub public_key: String,
+    pub weight: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuthoritySet {
+    pub set_id: u64,
+    pub authorities: Vec<Authority>,
+}
+
+/// Authority set change signalled by a header, which is
+/// yet to be enacted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuthorityChange {
+    /// Height at which the change is enacted.
+    pub activation_height: BlockNumber,
+    /// Hash and number of the header signalling the change.
+    pub enacting_block_hash: H256,
+    pub enacting_block_number: BlockNumber,
+    pub next_authorities: Vec<Authority>,
+    /// Median last finalized block number, set only for forced changes.
+    pub median_last_finalized: Option<BlockNumber>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct AuthoritySetResponse {
+    pub current_authority_set: AuthoritySet,
+    pub next_authority_change: Option<AuthorityChange>,
+    pub next_forced_authority_change: Option<AuthorityChange>,
+}
+
+/// BEEFY state, of which every part is `None` until BEEFY validator set is supplied.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct BeefyStateResponse {
+    pub validator_set: Option<BeefyValidatorSetResponse>,
+    /// Validator set announced by the latest MMR leaf finalizing a header.
+    pub next_authority_set: Option<BeefyNextAuthoritySetResponse>,
+    /// Latest MMR root signed by validators.
+    pub mmr_root: Option<BeefyMmrRootResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BeefyValidatorSetResponse {
+    pub id: u64,
+    /// Hex encoded compressed secp256k1 public keys.
+    pub validators: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BeefyNextAuthoritySetResponse {
+    pub id: u64,
+    pub len: u32,
+    /// Merkle root of validator public keys.
+    pub root: H256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BeefyMmrRootResponse {
+    pub block_number: BlockNumber,
+    pub root: H256,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct VerifyProofResponse {
+    pub verified: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct HeaderResponse {
+    pub header_height: BlockNumber,
+    pub header_hash: H256,
+    pub header_commitment_root: H256,
+    pub header_parent_hash: H256,
+    pub header_extrinsics_root: H256,
+    /// SCALE encoded digest items in hex.
+    pub header_digest_logs: Vec<String>,
+    /// SCALE encoded header in hex.
+    pub header: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct HeadersResponse {
+    pub headers: Vec<HeaderResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct StorageInfoResponse {
+    pub total_stored: u64,
+    pub oldest_stored_hash: H256,
+    pub max_headers_allowed_to_store: u64,
+    /// Inclusive ranges of block numbers skipped by headers
+    /// ingested ahead of the best header.
+    pub gaps: Vec<(BlockNumber, BlockNumber)>,
+    /// Numbers of headers retained as checkpoints after being pruned.
+    pub checkpoints: Vec<BlockNumber>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct CheckIntegrityResponse {
+    /// Inconsistencies found in light client storage. Empty if storage is consistent.
+    pub violations: Vec<IntegrityViolationResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct IntegrityViolationResponse {
+    /// Kind of inconsistency, e.g. `missing_header` or `lookup_mismatch`.
+    pub kind: String,
+    pub description: String,
+    /// Number of the header inconsistency refers to, if any.
+    pub header_height: Option<BlockNumber>,
+    /// Hash of the header inconsistency refers to, if any.
+    pub header_hash: Option<H256>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct ExportSnapshotResponse {
+    /// Versioned and checksummed SCALE encoded snapshot, in hex.
+    pub snapshot: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct StatusResponse {
+    pub status: Status,
+    pub trusting_period: u64,
+    pub last_finalized_header_timestamp: u64,
 }
@@ -0,0 +1,174 @@
+use parity_scale_codec::{Decode, Encode};
+use sp_core::hashing::blake2_256;
+
+use crate::common::types::blockchain_error::BlockchainError;
+use crate::contract::state::{HeaderFormat, TimestampCallIndex};
+use crate::db::Data;
+
+/// Version of snapshot format. It needs to be bumped whenever
+/// encoding of snapshot, or of anything it contains, changes.
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+const CHECKSUM_LENGTH: usize = 32;
+
+/// Client settings carried along with light client data in a snapshot.
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub struct SnapshotSettings {
+    pub name: String,
+    pub header_format: HeaderFormat,
+    pub max_headers_allowed_to_store: u64,
+    pub max_headers_allowed_between_justifications: u64,
+    pub headers_ingested_after_last_justification: u64,
+    pub trusting_period: u64,
+    pub last_finalized_header_timestamp: u64,
+    pub timestamp_call_index: TimestampCallIndex,
+}
+
+/// Light client database along with settings of the client it was exported
+/// from, which is enough to bootstrap another client in the same state.
+#[derive(Encode, Decode, Clone)]
+pub struct Snapshot {
+    pub settings: SnapshotSettings,
+    pub data: Data,
+}
+
+impl Snapshot {
+    /// Encodes snapshot prefixed with format version and followed
+    /// by Blake2-256 checksum of everything preceding it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SNAPSHOT_VERSION.encode();
+        self.encode_to(&mut bytes);
+        let checksum = blake2_256(&bytes);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    /// Decodes snapshot encoded by `to_bytes`, refusing it if checksum
+    /// doesn't match or if it is encoded in unsupported version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BlockchainError> {
+        if bytes.len() < SNAPSHOT_VERSION.encode().len() + CHECKSUM_LENGTH {
+            return Err(BlockchainError::DataDecode(
+                "Snapshot is too short".to_string(),
+            ));
+        }
+        let (content, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+        if blake2_256(content) != checksum {
+            return Err(BlockchainError::DataDecode(
+                "Snapshot checksum does not match its content".to_string(),
+            ));
+        }
+
+        let mut input = content;
+        let version = u16::decode(&mut input)
+            .map_err(|e| BlockchainError::DataDecode(e.what().to_string()))?;
+        if version != SNAPSHOT_VERSION {
+            return Err(BlockchainError::DataDecode(format!(
+                "Unsupported snapshot version: {}. Expected: {}",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+        let snapshot = Snapshot::decode(&mut input)
+            .map_err(|e| BlockchainError::DataDecode(e.what().to_string()))?;
+        if !input.is_empty() {
+            return Err(BlockchainError::DataDecode(
+                "Snapshot has trailing bytes".to_string(),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kvdb::KeyValueDB;
+    use parity_scale_codec::Encode;
+    use sp_core::hashing::blake2_256;
+
+    use crate::contract::snapshot::{Snapshot, SnapshotSettings, SNAPSHOT_VERSION};
+    use crate::contract::state::{BlockNumberType, HasherType, HeaderFormat, TimestampCallIndex};
+    use crate::db::{create, Data};
+
+    fn create_snapshot() -> Snapshot {
+        let db = create(2);
+        let mut transaction = db.transaction();
+        transaction.put(0, b"key1", b"horse");
+        transaction.put(1, b"key2", b"pigeon");
+        assert!(db.write(transaction).is_ok());
+
+        Snapshot {
+            settings: SnapshotSettings {
+                name: "testtesttest".into(),
+                header_format: HeaderFormat {
+                    block_number: BlockNumberType::U64,
+                    hasher: HasherType::Keccak256,
+                },
+                max_headers_allowed_to_store: 256,
+                max_headers_allowed_between_justifications: 2,
+                headers_ingested_after_last_justification: 1,
+                trusting_period: 100,
+                last_finalized_header_timestamp: 1000,
+                timestamp_call_index: TimestampCallIndex([3, 0]),
+            },
+            data: Data { db },
+        }
+    }
+
+    #[test]
+    fn snapshot_to_bytes_from_bytes() {
+        let snapshot = create_snapshot();
+        let bytes = snapshot.to_bytes();
+        // Encoding is deterministic
+        assert_eq!(bytes, create_snapshot().to_bytes());
+
+        let decoded_snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded_snapshot.settings, snapshot.settings);
+        assert_eq!(
+            decoded_snapshot.data.db.get(0, b"key1").unwrap().unwrap(),
+            b"horse"
+        );
+        assert_eq!(
+            decoded_snapshot.data.db.get(1, b"key2").unwrap().unwrap(),
+            b"pigeon"
+        );
+    }
+
+    #[test]
+    fn snapshot_from_bytes_fails_with_corrupted_or_unsupported_snapshot() {
+        let bytes = create_snapshot().to_bytes();
+
+        let mut corrupted_bytes = bytes.clone();
+        corrupted_bytes[10] ^= 1;
+        assert_eq!(
+            Snapshot::from_bytes(&corrupted_bytes)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Error while decoding data: Snapshot checksum does not match its content"
+        );
+
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 1])
+                .err()
+                .unwrap()
+                .to_string(),
+            "Error while decoding data: Snapshot checksum does not match its content"
+        );
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..3]).err().unwrap().to_string(),
+            "Error while decoding data: Snapshot is too short"
+        );
+
+        // Checksum is valid, but version isn't supported
+        let mut future_bytes = (SNAPSHOT_VERSION + 1).encode();
+        future_bytes.extend_from_slice(&bytes[2..bytes.len() - 32]);
+        let checksum = blake2_256(&future_bytes);
+        future_bytes.extend_from_slice(&checksum);
+        assert_eq!(
+            Snapshot::from_bytes(&future_bytes)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Error while decoding data: Unsupported snapshot version: 3. Expected: 2"
+        );
+    }
+}
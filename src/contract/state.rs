@@ -1,3 +1,6 @@
+use cosmwasm_std::CanonicalAddr;
+use parity_scale_codec::{Decode, Encode};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // This type is similar to primitive_types::H256 and
@@ -5,11 +8,130 @@ use serde::{Deserialize, Serialize};
 // make them independent to parity types.
 pub type H256 = Vec<u8>;
 
+// Block number wide enough to represent block number
+// of any supported header format.
+pub type BlockNumber = u64;
+
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ContractState {
     pub name: String,
+    /// Whole light client database in encoded form. Kept only by clients instantiated
+    /// before database was moved to prefixed storage, until contract is migrated.
+    #[serde(default)]
     pub light_client_data: Vec<u8>,
     pub headers_ingested_after_last_justification: u64,
     pub max_headers_allowed_between_justifications: u64,
     pub max_headers_allowed_to_store: u64,
+    /// Evidence of conflicting finalized headers. Once it is
+    /// recorded, client is frozen and refuses any further updates.
+    #[serde(default)]
+    pub misbehaviour: Option<Misbehaviour>,
+    /// Address allowed to recover frozen or expired client.
+    #[serde(default)]
+    pub admin: CanonicalAddr,
+    /// Period in seconds, counted from timestamp of the last finalized
+    /// header, after which client expires. Zero means it never expires.
+    #[serde(default)]
+    pub trusting_period: u64,
+    /// Timestamp in seconds of the last finalized header, as set
+    /// by timestamp inherent of its block. It never decreases.
+    #[serde(default)]
+    pub last_finalized_header_timestamp: u64,
+    /// Call index of `timestamp::set`, which timestamp inherent is recognized by.
+    #[serde(default)]
+    pub timestamp_call_index: TimestampCallIndex,
+}
+
+impl ContractState {
+    pub fn is_frozen(&self) -> bool {
+        self.misbehaviour.is_some()
+    }
+
+    /// Status of the client at given time in seconds.
+    pub fn status(&self, now: u64) -> Status {
+        if self.is_frozen() {
+            Status::Frozen
+        } else if self.trusting_period > 0 && now > self.expires_at() {
+            Status::Expired
+        } else {
+            Status::Active
+        }
+    }
+
+    /// Time in seconds after which client expires.
+    pub fn expires_at(&self) -> u64 {
+        self.last_finalized_header_timestamp
+            .saturating_add(self.trusting_period)
+    }
+}
+
+/// Lifecycle status of the client. Only active client accepts updates,
+/// while frozen or expired one need to be recovered first.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Active,
+    Frozen,
+    Expired,
+}
+
+/// Two headers at the same height, each finalized by a valid
+/// justification of the same authority set. Stored as submitted,
+/// in hex encoded form.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Misbehaviour {
+    pub header_a: String,
+    pub justification_a: String,
+    pub header_b: String,
+    pub justification_b: String,
+}
+
+/// Index of the timestamp pallet in the runtime, followed by index of its `set` call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Encode, Decode)]
+pub struct TimestampCallIndex(pub [u8; 2]);
+
+impl Default for TimestampCallIndex {
+    /// Call index of `timestamp::set` in Polkadot, Kusama and Westend runtimes.
+    fn default() -> Self {
+        TimestampCallIndex([2, 0])
+    }
+}
+
+/// Format of headers of the chain followed by the client, chosen
+/// when client is instantiated.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, Encode, Decode,
+)]
+pub struct HeaderFormat {
+    #[serde(default)]
+    pub block_number: BlockNumberType,
+    #[serde(default)]
+    pub hasher: HasherType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Encode, Decode)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockNumberType {
+    U32,
+    U64,
+}
+
+impl Default for BlockNumberType {
+    fn default() -> Self {
+        BlockNumberType::U32
+    }
+}
+
+/// Hashing algorithm used for header hashes and state trie.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Encode, Decode)]
+#[serde(rename_all = "lowercase")]
+pub enum HasherType {
+    Blake2_256,
+    Keccak256,
+}
+
+impl Default for HasherType {
+    fn default() -> Self {
+        HasherType::Blake2_256
+    }
 }
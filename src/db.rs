@@ -6,13 +6,15 @@ use parity_scale_codec::{Decode, Encode, Error, Input, Output};
 use parity_util_mem::MallocSizeOf;
 use parking_lot::RwLock;
 
-use crate::genesis::GenesisData;
 use parity_scale_codec::alloc::sync::Arc;
 
+/// Light client database in encoded form, as kept in contract state by clients
+/// instantiated before it was moved to prefixed storage, and carried by snapshots.
+/// It used to be followed by an empty genesis data placeholder, which encodes to
+/// nothing, so dropping it keeps encoding the same.
 #[derive(Encode, Decode, Clone)]
 pub struct Data {
     pub db: DB,
-    pub genesis_data: GenesisData,
 }
 
 #[derive(Default, MallocSizeOf)]
@@ -128,8 +130,11 @@ impl Encode for DB {
         let column_length = columns.len() as u32;
         column_length.encode_to(dest);
         for i in 0..column_length {
-            let column = columns.get(&i).unwrap();
-            column.encode_to(dest);
+            // Column missing from the middle is encoded as an empty one
+            match columns.get(&i) {
+                Some(column) => column.encode_to(dest),
+                None => BTreeMap::<Vec<u8>, DBValue>::new().encode_to(dest),
+            }
         }
     }
 }
@@ -160,7 +165,6 @@ mod tests {
     use parity_scale_codec::{Decode, Encode};
 
     use crate::db::{create, Data, DB};
-    use crate::genesis::GenesisData;
 
     #[test]
     fn db_encode_decode() {
@@ -189,10 +193,7 @@ mod tests {
         transaction.put(1, b"key3", b"cat");
         assert!(db.write(transaction).is_ok());
 
-        let data = Data {
-            db,
-            genesis_data: GenesisData {},
-        };
+        let data = Data { db };
 
         let data = data.encode();
         assert!(data.len() > 0);
@@ -251,10 +252,7 @@ mod tests {
         transaction.put(1, b"key3", b"cat");
         assert!(db.write(transaction).is_ok());
 
-        let data = Data {
-            db,
-            genesis_data: GenesisData {},
-        };
+        let data = Data { db };
 
         // First test: If two Data instance are identical, their
         // deserialization need to produce same binary data.
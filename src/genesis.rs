@@ -1,4 +0,0 @@
-use parity_scale_codec::{Decode, Encode};
-
-#[derive(Encode, Decode, Clone)]
-pub struct GenesisData {}
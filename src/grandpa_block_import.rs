@@ -1,3 +1,4 @@
+use crate::common::traits::aux_store::AuxStore;
 use crate::common::traits::block_import::BlockImport;
 use crate::common::traits::finalizer::Finalizer;
 use crate::common::traits::header_backend::HeaderBackend;
@@ -95,6 +96,18 @@ where
     let hash = block.header.hash();
     let number = block.header.number().clone();
 
+    // Header skipping ahead can't be linked to any header we have,
+    // so it needs to be proven finalized before it is imported.
+    if block.allow_gap {
+        let parent_hash = *block.header.parent_hash();
+        let possible_parent_header = client
+            .header(BlockId::Hash(parent_hash))
+            .map_err(|e| ConsensusError::ChainLookup(e.to_string()))?;
+        if possible_parent_header.is_none() {
+            return do_import_skipping_block::<_, _, _, J>(client, storage, block);
+        }
+    }
+
     // we don't want to finalize on `inner.import_block`
     let justification = block.justification.take();
     let import_result = client.import_block(block);
@@ -113,6 +126,41 @@ where
     }
 }
 
+/// Try to import block whose parent is unknown. Block is imported as
+/// finalized only if it comes with a valid justification.
+fn do_import_skipping_block<S, C, Block: BlockT, J>(
+    mut client: C,
+    storage: Arc<S>,
+    mut block: BlockImportParams<Block>,
+) -> Result<ImportResult, ConsensusError>
+where
+    C: HeaderBackend<Block> + BlockImport<Block>,
+    S: Storage<Block>,
+    NumberFor<Block>: finality_grandpa::BlockNumberOps,
+    J: ProvableJustification<Block>,
+{
+    let hash = block.header.hash();
+    let number = block.header.number().clone();
+
+    let justification = match block.justification.as_ref() {
+        Some(justification) => justification,
+        None => {
+            return Err(ConsensusError::ClientImport(
+                "Header skipping ahead of best header requires justification".into(),
+            ))
+        }
+    };
+
+    let (set_id, authorities) = fetch_current_authority_set(storage)?;
+    J::decode_and_verify_finalization(justification, set_id, (hash, number), &authorities)
+        .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+    block.finalized = true;
+    client
+        .import_block(block)
+        .map_err(|e| ConsensusError::ClientImport(e.to_string()))
+}
+
 /// Try to import justification.
 fn do_import_justification<S, C, Block: BlockT, J>(
     client: C,
@@ -127,20 +175,11 @@ where
     NumberFor<Block>: finality_grandpa::BlockNumberOps,
     J: ProvableJustification<Block>,
 {
-    let possible_light_authority_set =
-        fetch_light_authority_set(storage).map_err(|e| ConsensusError::Other(Box::new(e)))?;
-    if possible_light_authority_set.is_none() {
-        return Err(ConsensusError::InvalidAuthoritiesSet);
-    }
-    let light_authority_set = possible_light_authority_set.unwrap();
+    let (set_id, authorities) = fetch_current_authority_set(storage)?;
 
     // Verify if justification is valid and it finalizes correct block
-    let justification = J::decode_and_verify_finalization(
-        &justification,
-        light_authority_set.set_id(),
-        (hash, number),
-        &light_authority_set.authorities(),
-    );
+    let justification =
+        J::decode_and_verify_finalization(&justification, set_id, (hash, number), &authorities);
 
     // BadJustification error means that justification has been successfully decoded, but
     // it isn't valid within current authority set
@@ -160,6 +199,22 @@ where
     do_finalize_block(client, hash, number, justification.encode())
 }
 
+/// Fetch set id and authorities of the current authority set.
+fn fetch_current_authority_set<S: AuxStore>(
+    storage: Arc<S>,
+) -> Result<(u64, AuthorityList), ConsensusError> {
+    let possible_light_authority_set =
+        fetch_light_authority_set(storage).map_err(|e| ConsensusError::Other(Box::new(e)))?;
+    if possible_light_authority_set.is_none() {
+        return Err(ConsensusError::InvalidAuthoritiesSet);
+    }
+    let light_authority_set = possible_light_authority_set.unwrap();
+    Ok((
+        light_authority_set.set_id(),
+        light_authority_set.authorities(),
+    ))
+}
+
 /// Finalize the block.
 fn do_finalize_block<C, Block: BlockT>(
     client: C,
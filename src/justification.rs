@@ -38,24 +38,27 @@ impl From<BlockchainError> for Error {
 pub type Message<Block> = finality_grandpa::Message<<Block as BlockT>::Hash, NumberFor<Block>>;
 pub type Precommit<Block> = finality_grandpa::Precommit<<Block as BlockT>::Hash, NumberFor<Block>>;
 
-/// Justification used to prove block finality.
-pub trait ProvableJustification<Block: BlockT>: Encode + Decode {
+/// Justification used to prove block finality. It is verified against authorities of type
+/// `Authority`, which are GRANDPA voters along with their weights unless stated otherwise.
+pub trait ProvableJustification<Block: BlockT, Authority = (AuthorityId, u64)>:
+    Encode + Decode
+{
     /// Verify justification with respect to authorities set and authorities set id.
-    fn verify(&self, set_id: u64, authorities: &[(AuthorityId, u64)]) -> BlockchainResult<()>;
+    fn verify(&self, set_id: u64, authorities: &[Authority]) -> BlockchainResult<()>;
 
     /// Verify justification as well as check if it is targeting correct block
     fn verify_finalization(
         &self,
         set_id: u64,
         finalized_target: (Block::Hash, NumberFor<Block>),
-        authorities: &[(AuthorityId, u64)],
+        authorities: &[Authority],
     ) -> BlockchainResult<()>;
 
     fn decode_and_verify_finalization(
         justification: &Justification,
         set_id: u64,
         finalized_target: (Block::Hash, NumberFor<Block>),
-        authorities: &[(AuthorityId, u64)],
+        authorities: &[Authority],
     ) -> BlockchainResult<()> {
         let justification = Self::decode(&mut &**justification)
             .map_err(|_| BlockchainError::JustificationDecode)?;
@@ -219,7 +222,8 @@ where
     NumberFor<Block>: BlockNumberOps,
 {
     fn verify(&self, set_id: u64, authorities: &[(AuthorityId, u64)]) -> BlockchainResult<()> {
-        let voter_set = VoterSet::new(authorities.clone().to_owned().drain(..)).unwrap();
+        let voter_set = VoterSet::new(authorities.iter().cloned())
+            .ok_or(BlockchainError::InvalidAuthoritiesSet)?;
         GrandpaJustification::verify(self, set_id, &voter_set)
     }
 
@@ -229,7 +233,8 @@ where
         finalized_target: (Block::Hash, NumberFor<Block>),
         authorities: &[(AuthorityId, u64)],
     ) -> BlockchainResult<()> {
-        let voter_set = VoterSet::new(authorities.clone().to_owned().drain(..)).unwrap();
+        let voter_set = VoterSet::new(authorities.iter().cloned())
+            .ok_or(BlockchainError::InvalidAuthoritiesSet)?;
         GrandpaJustification::verify_finalization(self, set_id, finalized_target, &voter_set)?;
         Ok(())
     }
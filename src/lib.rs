@@ -5,18 +5,24 @@
 // Once phase 2 is complete, this will be removed.
 #![allow(dead_code)]
 
+mod aura_verifier;
+mod babe_verifier;
+mod beefy;
 mod block_import_wrapper;
 mod block_processor;
 mod client;
 mod common;
 mod db;
-mod genesis;
 mod grandpa_block_import;
 mod justification;
 mod light_state;
+mod migration;
+mod prefixed_db;
 mod storage;
+mod storage_proof;
 mod types;
 mod verifier;
+mod warp_sync;
 
 pub mod contract;
 pub use contract::msg;
@@ -26,7 +32,7 @@ pub use contract::msg;
 /// contract module.
 
 #[cfg(target_arch = "wasm32")]
-pub use wasm::{handle, init, query};
+pub use wasm::{handle, init, migrate, query};
 
 #[cfg(target_arch = "wasm32")]
 mod wasm {
@@ -55,6 +61,17 @@ mod wasm {
         )
     }
 
+    /// WASM Entry point for contract::migrate. It shares signature and result
+    /// encoding with handle, as cosmwasm-std 0.8 has no helper of its own for it.
+    #[no_mangle]
+    pub extern "C" fn migrate(env_ptr: u32, msg_ptr: u32) -> u32 {
+        do_handle(
+            &contract::migrate::<ExternalStorage, ExternalApi, ExternalQuerier>,
+            env_ptr,
+            msg_ptr,
+        )
+    }
+
     /// WASM Entry point for contract::query
     #[no_mangle]
     pub extern "C" fn query(msg_ptr: u32) -> u32 {
@@ -1,56 +1,146 @@
-use crate::block_processor::setup_block_processor;
+use crate::beefy::{validators_merkle_root, BeefyFinalityProof};
+use crate::block_processor::{setup_block_processor, BlockProcessor};
+use crate::client::Client;
+use crate::common::traits::finalizer::Finalizer;
 use crate::common::traits::header_backend::HeaderBackend;
 use crate::common::traits::storage::Storage as StorageT;
+use crate::common::types::aura_authority_set::AuraAuthoritySet;
+use crate::common::types::babe_epoch::BabeEpoch;
+use crate::common::types::beefy_validator_set::{
+    BeefyAuthorityId, BeefyMmrRoot, BeefyNextAuthoritySet, BeefyValidatorSet,
+};
 use crate::common::types::block_import_result::BlockImportResult;
 use crate::common::types::blockchain_error::BlockchainError;
 use crate::common::types::blockchain_info::BlockchainInfo;
 use crate::common::types::client_status::ClientStatus;
 use crate::common::types::incoming_block::IncomingBlock;
+use crate::common::types::integrity_violation::IntegrityViolation;
 use crate::common::types::light_authority_set::LightAuthoritySet;
 use crate::common::types::new_block_state::NewBlockState;
+use crate::common::types::pruning_policy::PruningPolicy;
+use crate::common::types::storage_info::StorageInfo;
 use crate::common::utils::{
-    fetch_light_authority_set, fetch_next_authority_change, initialize_storage,
-    insert_light_authority_set, NUM_COLUMNS,
+    delete_aura_authority_set, delete_babe_epochs, delete_beefy_commitment_data,
+    delete_next_authority_change, delete_next_babe_epoch, delete_next_forced_authority_change,
+    fetch_aura_authority_set, fetch_babe_epoch, fetch_beefy_mmr_root,
+    fetch_beefy_next_authority_set, fetch_beefy_validator_set, fetch_light_authority_set,
+    fetch_next_authority_change, fetch_next_babe_epoch, fetch_next_forced_authority_change,
+    initialize_storage, insert_aura_authority_set, insert_babe_epoch, insert_beefy_mmr_root,
+    insert_beefy_next_authority_set, insert_beefy_validator_set, insert_light_authority_set,
+    insert_next_babe_epoch, NUM_COLUMNS,
 };
-use crate::db::create;
-use crate::genesis::GenesisData;
-use crate::types::{Block, Header};
-use parity_scale_codec::Encode;
+use crate::db::{create, DB};
+use crate::justification::{GrandpaJustification, ProvableJustification};
+use crate::migration::migrate_db;
+use crate::storage::Storage;
+use crate::storage_proof::verify_read_proof;
+use crate::warp_sync::WarpSyncProof;
+use finality_grandpa::BlockNumberOps;
+use kvdb::KeyValueDB;
+use parity_scale_codec::{Decode, Encode};
 use sp_api::BlockId;
-use sp_runtime::traits::{Block as BlockT, NumberFor};
+use sp_runtime::traits::{
+    Block as BlockT, DigestFor, HashFor, Header as HeaderT, NumberFor, One, Zero,
+};
 use sp_runtime::Justification;
 
-/// Initializes the database with initial header
-/// and authority set
-pub(crate) fn initialize_state(
-    initial_header: Header,
+/// Initializes the database with initial header, authority set
+/// and policy deciding which headers are retained once pruned.
+pub(crate) fn initialize_state<Block, D>(
+    db: D,
+    initial_header: Block::Header,
     initial_authority_set: LightAuthoritySet,
+    pruning_policy: PruningPolicy,
     max_headers_allowed_to_store: u64,
-) -> Result<Vec<u8>, BlockchainError> {
-    let db = create(NUM_COLUMNS);
-    let new_data = crate::db::Data {
-        db,
-        genesis_data: GenesisData {},
-    };
-    let empty_data = new_data.encode();
-    let (data, storage) = initialize_storage(empty_data, max_headers_allowed_to_store)?;
+) -> Result<(), BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    let storage = initialize_storage(db, max_headers_allowed_to_store)?;
+    // Database might be initialized already, in which case
+    // light client starts over from scratch.
+    storage.clear::<Block>()?;
+    delete_next_authority_change(storage.clone())?;
+    delete_next_forced_authority_change(storage.clone())?;
     insert_light_authority_set(storage.clone(), initial_authority_set)?;
-    StorageT::<Block>::import_header(storage.as_ref(), initial_header, NewBlockState::Best)?;
+    storage.set_pruning_policy(&pruning_policy)?;
+    StorageT::<Block>::import_header(storage.as_ref(), initial_header, NewBlockState::Best)
+}
+
+/// Stores BABE epoch in effect at the trusted header, and the one following it if it is
+/// already announced. Once stored, headers ingested without justification need to be
+/// sealed by BABE authorities of their slots, instead of Aura ones.
+pub(crate) fn initialize_babe_epochs<D>(
+    db: D,
+    epoch: BabeEpoch,
+    possible_next_epoch: Option<BabeEpoch>,
+) -> Result<(), BlockchainError>
+where
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only writing aux storage
+    let storage = initialize_storage(db, 2)?;
+    // Headers are sealed by either BABE or Aura
+    delete_aura_authority_set(storage.clone())?;
+    insert_babe_epoch(storage.clone(), &epoch)?;
+    match possible_next_epoch {
+        Some(next_epoch) => insert_next_babe_epoch(storage, &next_epoch),
+        None => delete_next_babe_epoch(storage),
+    }
+}
+
+/// Gives BABE epoch in effect and the one announced to follow it, if BABE seals are verified.
+pub(crate) fn babe_epochs<D>(
+    db: D,
+) -> Result<(Option<BabeEpoch>, Option<BabeEpoch>), BlockchainError>
+where
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading aux storage
+    let storage = initialize_storage(db, 2)?;
+    Ok((
+        fetch_babe_epoch(storage.clone())?,
+        fetch_next_babe_epoch(storage)?,
+    ))
+}
 
-    Ok(data.encode())
+/// Stores Aura authority set in effect after the trusted header. Once stored, headers ingested
+/// without justification need to be sealed by Aura authorities of their slots, instead of BABE ones.
+pub(crate) fn initialize_aura_authority_set<D>(
+    db: D,
+    aura_authority_set: AuraAuthoritySet,
+) -> Result<(), BlockchainError>
+where
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only writing aux storage
+    let storage = initialize_storage(db, 2)?;
+    // Headers are sealed by either BABE or Aura
+    delete_babe_epochs(storage.clone())?;
+    insert_aura_authority_set(storage, &aura_authority_set)
+}
+
+/// Gives Aura authority set in effect, if Aura seals are verified.
+pub(crate) fn aura_authority_set<D>(db: D) -> Result<Option<AuraAuthoritySet>, BlockchainError>
+where
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading aux storage
+    let storage = initialize_storage(db, 2)?;
+    fetch_aura_authority_set(storage)
 }
 
 /// Gives current status of database passed which includes
 /// current best header, finalized header, light authority set
 /// as well as next authority set change scheduled.
-pub(crate) fn current_status<Block>(
-    encoded_data: Vec<u8>,
-) -> Result<ClientStatus<Block>, BlockchainError>
+pub(crate) fn current_status<Block, D>(db: D) -> Result<ClientStatus<Block>, BlockchainError>
 where
     Block: BlockT,
+    D: KeyValueDB,
 {
     // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage meta
-    let (_, storage) = initialize_storage(encoded_data, 2)?;
+    let storage = initialize_storage(db, 2)?;
     let possible_light_authority_set = fetch_light_authority_set(storage.clone())?;
     let mut possible_finalized_header: Option<Block::Header> = None;
     let mut possible_best_header: Option<Block::Header> = None;
@@ -62,39 +152,615 @@ where
         possible_best_header = storage.header(BlockId::<Block>::Hash(info.best_hash))?;
     }
     let possible_next_change_in_authority = fetch_next_authority_change(storage.clone())?;
+    let possible_next_forced_change_in_authority =
+        fetch_next_forced_authority_change(storage.clone())?;
+    let gaps = storage.gaps::<Block>()?;
 
     Ok(ClientStatus {
         possible_last_finalized_header: possible_finalized_header,
         possible_light_authority_set,
         possible_next_change_in_authority,
+        possible_next_forced_change_in_authority,
         possible_best_header,
+        gaps,
     })
 }
 
+/// Gives stored header with given number, if it is still
+/// present in the storage.
+pub(crate) fn header_by_number<Block, D>(
+    db: D,
+    number: NumberFor<Block>,
+) -> Result<Option<Block::Header>, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    storage.header(BlockId::<Block>::Number(number))
+}
+
+/// Gives stored header with given hash, if it is still
+/// present in the storage.
+pub(crate) fn header_by_hash<Block, D>(
+    db: D,
+    hash: Block::Hash,
+) -> Result<Option<Block::Header>, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    storage.header(BlockId::<Block>::Hash(hash))
+}
+
+/// Gives at most `limit` stored headers with numbers in the inclusive range
+/// `from..=to`, in ascending order. Numbers which are pruned, discarded
+/// or skipped by a gap are left out.
+pub(crate) fn headers_in_range<Block, D>(
+    db: D,
+    from: NumberFor<Block>,
+    to: NumberFor<Block>,
+    limit: usize,
+) -> Result<Vec<Block::Header>, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    let info: BlockchainInfo<Block> = storage.info();
+    let oldest_stored_hash = storage.storage_info::<Block>()?.oldest_stored_hash;
+    let possible_oldest_stored_header =
+        storage.header(BlockId::<Block>::Hash(oldest_stored_hash))?;
+    if possible_oldest_stored_header.is_none() {
+        return Ok(vec![]);
+    }
+
+    // There is nothing stored outside of oldest and best header, so no need to look it up
+    let from = from.max(*possible_oldest_stored_header.unwrap().number());
+    let to = to.min(info.best_number);
+
+    // Numbers skipped by gaps are jumped over instead of being looked up
+    let gaps = storage.gaps::<Block>()?;
+    let mut number = gaps
+        .iter()
+        .find(|(start, end)| *start <= from && from <= *end)
+        .map_or(from, |(_, end)| *end + One::one());
+    let mut headers = vec![];
+    while number <= to && headers.len() < limit {
+        if let Some(header) = storage.header(BlockId::<Block>::Number(number))? {
+            headers.push(header);
+        }
+        if number == to {
+            break;
+        }
+        number = Storage::<D>::next_stored_number::<Block>(number, &gaps);
+    }
+    Ok(headers)
+}
+
+/// Gives policy deciding which headers are retained once pruned.
+pub(crate) fn pruning_policy<D>(db: D) -> Result<PruningPolicy, BlockchainError>
+where
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    storage.pruning_policy()
+}
+
+/// Gives number of headers stored at the moment, hash
+/// of the oldest one and headers retained as checkpoints.
+pub(crate) fn stored_headers_info<Block, D>(db: D) -> Result<StorageInfo<Block>, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage meta
+    let storage = initialize_storage(db, 2)?;
+    storage.storage_info::<Block>()
+}
+
+/// Checks stored headers against storage metadata, lookup entries and aux
+/// storage, giving every inconsistency found.
+pub(crate) fn check_integrity<Block, D>(
+    db: D,
+) -> Result<Vec<IntegrityViolation<NumberFor<Block>, Block::Hash>>, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    storage.check_integrity::<Block>()
+}
+
+/// Upgrades state persisted in the database to the current format,
+/// giving the version it was at before.
+pub(crate) fn migrate_state<Block, D>(db: D) -> Result<u16, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    migrate_db::<Block, _>(&db)
+}
+
+/// Copies light client database into in-memory one, so that it can be exported.
+pub(crate) fn export_db<Block, D>(db: D) -> Result<DB, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    let exported_db = create(NUM_COLUMNS);
+    storage.export_into::<Block, _>(&exported_db)?;
+    Ok(exported_db)
+}
+
+/// Verifies that storage proof proves `expected_value` stored under given key in the state
+/// of header at given height, or absence of the key if `expected_value` is `None`, using
+/// only trie nodes supplied in the proof. Header at given height need to be still present
+/// in the storage.
+pub(crate) fn verify_storage_proof<Block, D>(
+    db: D,
+    height: NumberFor<Block>,
+    key: &[u8],
+    expected_value: Option<&[u8]>,
+    proof: Vec<Vec<u8>>,
+) -> Result<bool, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    let possible_header = header_by_number::<Block, D>(db, height)?;
+    if possible_header.is_none() {
+        return Err(BlockchainError::UnknownBlock(format!(
+            "Header at height {} is not stored",
+            height
+        )));
+    }
+    let header = possible_header.unwrap();
+    verify_read_proof::<HashFor<Block>>(header.state_root(), proof, key, expected_value)
+}
+
+/// Verifies that given headers are at the same height, but different, and that
+/// each one of them is finalized by its justification signed by the current
+/// authority set. Returns an error if it isn't a proof of misbehaviour.
+pub(crate) fn verify_misbehaviour<Block, D>(
+    db: D,
+    header_a: Block::Header,
+    justification_a: Justification,
+    header_b: Block::Header,
+    justification_b: Justification,
+) -> Result<(), BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+    NumberFor<Block>: BlockNumberOps,
+    DigestFor<Block>: Encode,
+{
+    if header_a.number() != header_b.number() {
+        return Err(BlockchainError::Msg(format!(
+            "Headers need to be at the same height. Got: {} and {}",
+            header_a.number(),
+            header_b.number()
+        )));
+    }
+    if header_a.hash() == header_b.hash() {
+        return Err(BlockchainError::Msg(
+            "Headers need to be different to prove misbehaviour".into(),
+        ));
+    }
+
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading the storage
+    let storage = initialize_storage(db, 2)?;
+    let possible_light_authority_set = fetch_light_authority_set(storage)?;
+    if possible_light_authority_set.is_none() {
+        return Err(BlockchainError::InvalidAuthoritiesSet);
+    }
+    let light_authority_set = possible_light_authority_set.unwrap();
+
+    for (header, justification) in &[(header_a, justification_a), (header_b, justification_b)] {
+        GrandpaJustification::<Block>::decode_and_verify_finalization(
+            justification,
+            light_authority_set.set_id(),
+            (header.hash(), *header.number()),
+            &light_authority_set.authorities(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Finalizes already ingested header, along with its ancestors which are not finalized
+/// yet, once justification finalizing it is verified against the current authority set.
+/// Gives number and hash of the finalized header.
+pub(crate) fn apply_justification<Block, D>(
+    db: D,
+    justification: Justification,
+) -> Result<(NumberFor<Block>, Block::Hash), BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+    NumberFor<Block>: BlockNumberOps,
+{
+    let grandpa_justification =
+        GrandpaJustification::<Block>::decode(&mut justification.as_slice())
+            .map_err(|_| BlockchainError::JustificationDecode)?;
+    let hash = grandpa_justification.commit.target_hash;
+    let number = grandpa_justification.commit.target_number;
+
+    // It doesn't matter what is the value of max_headers_allowed_to_store as finalization doesn't prune headers
+    let storage = initialize_storage(db, 2)?;
+    ensure_finalizable::<Block, _>(storage.as_ref(), hash, number, "justification")?;
+
+    let possible_light_authority_set = fetch_light_authority_set(storage.clone())?;
+    if possible_light_authority_set.is_none() {
+        return Err(BlockchainError::InvalidAuthoritiesSet);
+    }
+    let light_authority_set = possible_light_authority_set.unwrap();
+    GrandpaJustification::<Block>::decode_and_verify_finalization(
+        &justification,
+        light_authority_set.set_id(),
+        (hash, number),
+        &light_authority_set.authorities(),
+    )?;
+
+    let client = Client::new(storage);
+    client.finalize_block(BlockId::<Block>::Hash(hash), Some(justification))?;
+    Ok((number, hash))
+}
+
+/// Stores BEEFY validator set in effect at the trusted header. Once stored, headers
+/// ingested without justification can be finalized by BEEFY finality proofs instead.
+pub(crate) fn initialize_beefy_validator_set<D>(
+    db: D,
+    validator_set: BeefyValidatorSet,
+) -> Result<(), BlockchainError>
+where
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only writing aux storage
+    let storage = initialize_storage(db, 2)?;
+    delete_beefy_commitment_data(storage.clone())?;
+    insert_beefy_validator_set(storage, &validator_set)
+}
+
+/// Gives BEEFY validator set in effect, the next one announced by MMR leaves
+/// and the latest MMR root signed by validators, if BEEFY is tracked.
+pub(crate) fn beefy_state<Block, D>(
+    db: D,
+) -> Result<
+    (
+        Option<BeefyValidatorSet>,
+        Option<BeefyNextAuthoritySet>,
+        Option<BeefyMmrRoot<NumberFor<Block>>>,
+    ),
+    BlockchainError,
+>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    // It doesn't matter what is the value of max_headers_allowed_to_store as we are only reading aux storage
+    let storage = initialize_storage(db, 2)?;
+    Ok((
+        fetch_beefy_validator_set(storage.clone())?,
+        fetch_beefy_next_authority_set(storage.clone())?,
+        fetch_beefy_mmr_root::<_, Block>(storage)?,
+    ))
+}
+
+/// Finalizes already ingested header, along with its ancestors which are not finalized yet,
+/// once BEEFY finality proof committing to it is verified against the current validator set
+/// or the next one announced by MMR leaves. As leaves only carry merkle root of public keys,
+/// validators of the next set need to be supplied. Gives number and hash of the finalized header.
+pub(crate) fn apply_beefy_finality_proof<Block, D>(
+    db: D,
+    finality_proof: Vec<u8>,
+    possible_next_validators: Option<Vec<BeefyAuthorityId>>,
+) -> Result<(NumberFor<Block>, Block::Hash), BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    let beefy_finality_proof = BeefyFinalityProof::<Block>::decode(&mut finality_proof.as_slice())
+        .map_err(|_| BlockchainError::JustificationDecode)?;
+    let (number, hash) = beefy_finality_proof.leaf.parent_number_and_hash;
+
+    // It doesn't matter what is the value of max_headers_allowed_to_store as finalization doesn't prune headers
+    let storage = initialize_storage(db, 2)?;
+    ensure_finalizable::<Block, _>(storage.as_ref(), hash, number, "BEEFY finality proof")?;
+
+    let possible_validator_set = fetch_beefy_validator_set(storage.clone())?;
+    if possible_validator_set.is_none() {
+        return Err(BlockchainError::InvalidAuthoritiesSet);
+    }
+    let current_validator_set = possible_validator_set.unwrap();
+    let set_id = beefy_finality_proof
+        .signed_commitment
+        .commitment
+        .validator_set_id;
+    let validator_set = if set_id == current_validator_set.id {
+        current_validator_set.clone()
+    } else {
+        match fetch_beefy_next_authority_set(storage.clone())? {
+            Some(next_authority_set) if next_authority_set.id == set_id => {
+                let next_validators = possible_next_validators.ok_or_else(|| {
+                    BlockchainError::Msg(format!(
+                        "Validators of BEEFY validator set {} need to be supplied",
+                        set_id
+                    ))
+                })?;
+                if next_validators.len() != next_authority_set.len as usize
+                    || validators_merkle_root(&next_validators) != next_authority_set.root
+                {
+                    return Err(BlockchainError::Msg(format!(
+                        "Supplied validators do not match BEEFY validator set {} announced in MMR leaf",
+                        set_id
+                    )));
+                }
+                BeefyValidatorSet::new(set_id, next_validators)
+            }
+            _ => {
+                return Err(BlockchainError::Msg(format!(
+                    "BEEFY validator set {} is not known",
+                    set_id
+                )))
+            }
+        }
+    };
+    beefy_finality_proof.verify_finalization(
+        validator_set.id,
+        (hash, number),
+        &validator_set.validators,
+    )?;
+
+    if validator_set.id != current_validator_set.id {
+        insert_beefy_validator_set(storage.clone(), &validator_set)?;
+    }
+    let next_authority_set = &beefy_finality_proof.leaf.beefy_next_authority_set;
+    if next_authority_set.id > validator_set.id {
+        insert_beefy_next_authority_set(storage.clone(), next_authority_set)?;
+    }
+    let commitment = &beefy_finality_proof.signed_commitment.commitment;
+    let is_newer_mmr_root = match fetch_beefy_mmr_root::<_, Block>(storage.clone())? {
+        Some(mmr_root) => commitment.block_number > mmr_root.block_number,
+        None => true,
+    };
+    if is_newer_mmr_root {
+        insert_beefy_mmr_root::<_, Block>(
+            storage.clone(),
+            &BeefyMmrRoot {
+                block_number: commitment.block_number,
+                root: commitment.payload,
+            },
+        )?;
+    }
+
+    let client = Client::new(storage);
+    client.finalize_block(BlockId::<Block>::Hash(hash), None)?;
+    Ok((number, hash))
+}
+
+/// Checks that header is stored, but neither it nor its descendants are finalized yet.
+fn ensure_finalizable<Block, D>(
+    storage: &Storage<D>,
+    hash: Block::Hash,
+    number: NumberFor<Block>,
+    proof_name: &str,
+) -> Result<(), BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    if storage.header(BlockId::<Block>::Hash(hash))?.is_none() {
+        return Err(BlockchainError::UnknownBlock(format!(
+            "Header {} finalized by {} is not stored",
+            number, proof_name
+        )));
+    }
+    if number <= last_finalized_number::<Block, _>(storage)? {
+        return Err(BlockchainError::Msg(format!(
+            "Header {} is already finalized",
+            number
+        )));
+    }
+    Ok(())
+}
+
+fn last_finalized_number<Block, D>(
+    storage: &Storage<D>,
+) -> Result<NumberFor<Block>, BlockchainError>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    let info: BlockchainInfo<Block> = storage.info();
+    // Initial header is trusted, so it is treated as finalized
+    if info.finalized_hash == Default::default() {
+        Ok(storage
+            .number(info.genesis_hash)?
+            .unwrap_or_else(Zero::zero))
+    } else {
+        Ok(info.finalized_number)
+    }
+}
+
 /// Ingests finalized header and optionally a justification
 /// Until justification is not provided block won't be marked as
 /// finalized. And if there are already `max_non_finalized_blocks`
 /// in db, it won't accept another header.
-pub(crate) fn ingest_finalized_header(
-    encoded_data: Vec<u8>,
-    finalized_header: Header,
+pub(crate) fn ingest_finalized_header<Block, D>(
+    db: D,
+    finalized_header: Block::Header,
     justification: Option<Justification>,
     max_non_finalized_blocks_allowed: u64,
-) -> Result<(BlockImportResult<NumberFor<Block>>, Vec<u8>), String> {
-    let (mut block_processor_fn, data) =
-        setup_block_processor(encoded_data, max_non_finalized_blocks_allowed)
+) -> Result<BlockImportResult<NumberFor<Block>>, String>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+    NumberFor<Block>: BlockNumberOps,
+    DigestFor<Block>: Encode,
+{
+    let mut block_processor_fn =
+        setup_block_processor::<Block, D>(db, max_non_finalized_blocks_allowed)
+            .map_err(|e| format!("{}", e))?;
+
+    let block_import_response = import_header(
+        &mut block_processor_fn,
+        finalized_header,
+        justification,
+        false,
+    )?;
+    Ok(block_import_response)
+}
+
+/// Ingests header along with the justification finalizing it. Unlike
+/// `ingest_finalized_header`, header doesn't need to be a child of the best
+/// header. Headers between last finalized header and ingested header are
+/// recorded as a gap, and headers which are not finalized are discarded.
+pub(crate) fn ingest_justified_header<Block, D>(
+    db: D,
+    justified_header: Block::Header,
+    justification: Justification,
+    max_non_finalized_blocks_allowed: u64,
+) -> Result<BlockImportResult<NumberFor<Block>>, String>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+    NumberFor<Block>: BlockNumberOps,
+    DigestFor<Block>: Encode,
+{
+    let mut block_processor_fn =
+        setup_block_processor::<Block, D>(db, max_non_finalized_blocks_allowed)
+            .map_err(|e| format!("{}", e))?;
+
+    let block_import_response = import_header(
+        &mut block_processor_fn,
+        justified_header,
+        Some(justification),
+        true,
+    )?;
+    Ok(block_import_response)
+}
+
+/// Moves authority set forward by warp sync proof, whose fragments are finalized by authority
+/// sets scheduled by the previous ones, and ingests header of the last fragment the same way
+/// `ingest_justified_header` does. Pending authority set changes are discarded once the proof
+/// moves authority set forward, as the proof supersedes them. Gives the ingested header.
+pub(crate) fn apply_warp_sync_proof<Block, D>(
+    db: D,
+    warp_sync_proof: Vec<u8>,
+    max_non_finalized_blocks_allowed: u64,
+) -> Result<(Block::Header, BlockImportResult<NumberFor<Block>>), String>
+where
+    Block: BlockT,
+    D: KeyValueDB + Clone,
+    NumberFor<Block>: BlockNumberOps,
+    DigestFor<Block>: Encode,
+{
+    let warp_sync_proof = WarpSyncProof::<Block>::decode(&mut warp_sync_proof.as_slice())
+        .map_err(|_| "Unable to decode warp sync proof".to_string())?;
+
+    let storage = initialize_storage(db.clone(), max_non_finalized_blocks_allowed)
+        .map_err(|e| format!("{}", e))?;
+    let current_authority_set = fetch_light_authority_set(storage.clone())
+        .map_err(|e| format!("{}", e))?
+        .ok_or_else(|| format!("{}", BlockchainError::InvalidAuthoritiesSet))?;
+    // Fragments need to be finalized by authority sets following the current one
+    if let Some(first_fragment) = warp_sync_proof.proofs.first() {
+        let first_number = *first_fragment.header.number();
+        let finalized_number =
+            last_finalized_number::<Block, _>(storage.as_ref()).map_err(|e| format!("{}", e))?;
+        if first_number <= finalized_number {
+            return Err(format!("Header {} is already finalized", first_number));
+        }
+    }
+    let (target_fragment, finalizing_authority_set, next_authority_set) = warp_sync_proof
+        .verify(&current_authority_set)
+        .map_err(|e| format!("{}", e))?;
+
+    if finalizing_authority_set.set_id() != current_authority_set.set_id() {
+        delete_next_authority_change(storage.clone())
+            .and_then(|_| delete_next_forced_authority_change(storage.clone()))
+            .and_then(|_| {
+                insert_light_authority_set(storage.clone(), finalizing_authority_set.clone())
+            })
+            .map_err(|e| format!("{}", e))?;
+    }
+
+    let mut block_processor_fn =
+        setup_block_processor::<Block, D>(db, max_non_finalized_blocks_allowed)
+            .map_err(|e| format!("{}", e))?;
+    let target_header = target_fragment.header.clone();
+    let block_import_response = import_header(
+        &mut block_processor_fn,
+        target_header.clone(),
+        Some(target_fragment.justification.encode()),
+        true,
+    )?;
+
+    // Change scheduled by the target header is enacted at it, so it isn't left pending
+    if next_authority_set.set_id() != finalizing_authority_set.set_id() {
+        delete_next_authority_change(storage.clone())
+            .and_then(|_| insert_light_authority_set(storage, next_authority_set))
+            .map_err(|e| format!("{}", e))?;
+    }
+    Ok((target_header, block_import_response))
+}
+
+/// Ingests multiple headers, each with an optional justification, in the given order.
+/// If any header in the batch fails to be ingested, changes made to the database
+/// need to be discarded by the caller, so that batch is applied atomically.
+pub(crate) fn ingest_finalized_headers<Block, D>(
+    db: D,
+    headers: Vec<(Block::Header, Option<Justification>)>,
+    max_non_finalized_blocks_allowed: u64,
+) -> Result<Vec<BlockImportResult<NumberFor<Block>>>, String>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+    NumberFor<Block>: BlockNumberOps,
+    DigestFor<Block>: Encode,
+{
+    let mut block_processor_fn =
+        setup_block_processor::<Block, D>(db, max_non_finalized_blocks_allowed)
             .map_err(|e| format!("{}", e))?;
+
+    let mut block_import_responses = vec![];
+    for (header, justification) in headers {
+        let number = *header.number();
+        let block_import_response =
+            import_header(&mut block_processor_fn, header, justification, false)
+                .map_err(|e| format!("Header {}: {}", number, e))?;
+        block_import_responses.push(block_import_response);
+    }
+    Ok(block_import_responses)
+}
+
+fn import_header<Block>(
+    block_processor_fn: &mut BlockProcessor<'_, Block>,
+    header: Block::Header,
+    justification: Option<Justification>,
+    allow_gap: bool,
+) -> Result<BlockImportResult<NumberFor<Block>>, String>
+where
+    Block: BlockT,
+{
     let incoming_block = IncomingBlock {
-        hash: finalized_header.hash(),
-        header: Some(finalized_header),
+        hash: header.hash(),
+        header: Some(header),
         body: None,
         justification,
         allow_missing_state: false,
+        allow_gap,
         import_existing: false,
     };
 
-    // We aren't returning updated db data from block processor function directly, because
-    // in future we might want to call it for multiple blocks per tx.
     let block_import_response = block_processor_fn(incoming_block)?;
     match &block_import_response {
         BlockImportResult::ImportedKnown(_) => {}
@@ -107,27 +773,53 @@ pub(crate) fn ingest_finalized_header(
             }
         }
     }
-    Ok((block_import_response, data.encode()))
+    Ok(block_import_response)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::aura_verifier::{ConsensusLog as AuraConsensusLog, AURA_ENGINE_ID};
+    use crate::babe_verifier::{
+        make_transcript, ConsensusLog as BabeConsensusLog, NextEpochDescriptor, PreDigest,
+        PrimaryPreDigest, SecondaryPreDigest, BABE_ENGINE_ID,
+    };
+    use crate::beefy::{
+        validators_merkle_root, BeefyFinalityProof, Commitment, MmrLeaf, MmrLeafProof,
+        SignedCommitment,
+    };
+    use crate::common::types::aura_authority_set::{AuraAuthoritySet, AuraCrypto};
+    use crate::common::types::babe_epoch::BabeEpoch;
+    use crate::common::types::beefy_validator_set::{BeefyNextAuthoritySet, BeefyValidatorSet};
     use crate::common::types::light_authority_set::LightAuthoritySet;
+    use crate::common::types::pruning_policy::PruningPolicy;
+    use crate::common::utils::NUM_COLUMNS;
+    use crate::db::{create, DB};
     use crate::justification::{Commit, GrandpaJustification, Message, Precommit};
-    use crate::light_state::{current_status, ingest_finalized_header, initialize_state};
-    use crate::types::{Block, Header};
+    use crate::light_state::{
+        apply_beefy_finality_proof, apply_justification, apply_warp_sync_proof, aura_authority_set,
+        babe_epochs, beefy_state, current_status, header_by_hash, header_by_number,
+        headers_in_range, ingest_finalized_header, ingest_finalized_headers,
+        ingest_justified_header, initialize_aura_authority_set, initialize_babe_epochs,
+        initialize_beefy_validator_set, initialize_state, stored_headers_info, verify_misbehaviour,
+        verify_storage_proof,
+    };
+    use crate::types::{Block, Header, U64KeccakBlock, U64KeccakHeader};
+    use crate::warp_sync::{WarpSyncFragment, WarpSyncProof};
     use clear_on_drop::clear::Clear;
     use finality_grandpa::SignedPrecommit;
-    use parity_scale_codec::Encode;
+    use parity_scale_codec::{Decode, Encode};
+    use schnorrkel::{ExpansionMode, MiniSecretKey};
     use sp_core::crypto::Public;
-    use sp_core::H256;
+    use sp_core::hashing::keccak_256;
+    use sp_core::{ecdsa, ed25519, sr25519, Pair, H256};
     use sp_finality_grandpa::{
         AuthorityId, AuthorityList, AuthoritySignature, ScheduledChange, GRANDPA_ENGINE_ID,
     };
     use sp_keyring::ed25519::Keyring;
     use sp_keyring::Ed25519Keyring;
-    use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, One};
+    use sp_runtime::traits::{BlakeTwo256, Block as BlockT, Header as HeaderT, NumberFor, One};
     use sp_runtime::{DigestItem, Justification};
+    use sp_trie::{Layout, MemoryDB, TrieDBMut, TrieMut};
     use std::io::Write;
     use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
@@ -276,9 +968,16 @@ mod tests {
             ),
         );
 
-        let result = initialize_state(initial_header.clone(), authority_set, 2);
+        let db = create(NUM_COLUMNS);
+        let result = initialize_state::<Block, _>(
+            db.clone(),
+            initial_header.clone(),
+            authority_set,
+            PruningPolicy::default(),
+            2,
+        );
         assert!(result.is_ok());
-        let encoded_data = result.unwrap();
+        let encoded_data = db.encode();
         assert!(encoded_data.len() > 0);
         // Best header need to be updated
         internal_assert_best_header(encoded_data.clone(), &initial_header);
@@ -317,9 +1016,11 @@ mod tests {
             ),
         );
 
-        let result = ingest_finalized_header(encoded_data, header.clone(), justification, 256);
+        let db = decode_db(&encoded_data);
+        let result =
+            ingest_finalized_header::<Block, _>(db.clone(), header.clone(), justification, 256);
         assert!(result.is_ok());
-        let encoded_data = result.unwrap().1;
+        let encoded_data = db.encode();
         // Best header need to be updated
         internal_assert_best_header(encoded_data.clone(), &header);
 
@@ -358,7 +1059,12 @@ mod tests {
             ),
         );
 
-        let result = ingest_finalized_header(encoded_data, header.clone(), justification, 256);
+        let result = ingest_finalized_header::<Block, _>(
+            decode_db(&encoded_data),
+            header.clone(),
+            justification,
+            256,
+        );
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), expected_error);
 
@@ -375,6 +1081,12 @@ mod tests {
         );
     }
 
+    // Light state operates on database in place, so database is kept encoded
+    // in tests to be able to go back to any of its previous states.
+    fn decode_db(encoded_data: &[u8]) -> DB {
+        DB::decode(&mut &encoded_data[..]).unwrap()
+    }
+
     fn create_next_header(header: Header) -> Header {
         let mut next_header = header.clone();
         next_header.number += 1;
@@ -384,7 +1096,7 @@ mod tests {
     }
 
     fn internal_assert_best_header(encoded_data: Vec<u8>, expected_to_be_best_header: &Header) {
-        let result = current_status::<Block>(encoded_data.clone());
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
         assert!(result.is_ok());
         let status = result.unwrap();
         assert!(status.possible_best_header.is_some());
@@ -409,7 +1121,7 @@ mod tests {
             ),
         );
 
-        let result = current_status::<Block>(encoded_data.clone());
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
         assert!(result.is_ok());
         let status = result.unwrap();
         assert!(status.possible_last_finalized_header.is_some());
@@ -446,7 +1158,7 @@ mod tests {
             ),
         );
 
-        let result = current_status::<Block>(encoded_data.clone());
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
         assert!(result.is_ok());
         let status = result.unwrap();
         assert!(status.possible_light_authority_set.is_some());
@@ -490,7 +1202,7 @@ mod tests {
             ),
         );
 
-        let result = current_status::<Block>(encoded_data.clone());
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
         assert!(result.is_ok());
         let status = result.unwrap();
         assert!(status.possible_next_change_in_authority.is_some());
@@ -513,7 +1225,7 @@ mod tests {
             format!("========= Verifying absence of next change of authority =========="),
         );
 
-        let result = current_status::<Block>(encoded_data.clone());
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
         assert!(result.is_ok());
         let status = result.unwrap();
         assert!(status.possible_next_change_in_authority.is_none());
@@ -528,6 +1240,66 @@ mod tests {
         );
     }
 
+    fn assert_next_forced_change_in_authority(
+        encoded_data: Vec<u8>,
+        expected_forced_change: &ScheduledChange<NumberFor<Block>>,
+        expected_median_last_finalized: NumberFor<Block>,
+        print_level: PrintLevel,
+    ) {
+        write_assert_guards(
+            print_level,
+            format!("========= Verifying existence of next forced change of authority =========="),
+        );
+        write_neutral_assert(
+            print_level,
+            format!(
+                "Checking if forced change is updated to {:?}",
+                expected_forced_change
+            ),
+        );
+
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
+        assert!(result.is_ok());
+        let status = result.unwrap();
+        assert!(status.possible_next_forced_change_in_authority.is_some());
+        let forced_change = status.possible_next_forced_change_in_authority.unwrap();
+        assert_eq!(&forced_change.change, expected_forced_change);
+        assert_eq!(
+            forced_change.median_last_finalized,
+            Some(expected_median_last_finalized)
+        );
+
+        write_success_assert(
+            print_level,
+            format!("Forced change is updated to {:?}", forced_change.change),
+        );
+        write_assert_guards(
+            print_level,
+            format!("========= Verified existence of next forced change of authority =========="),
+        );
+    }
+
+    fn assert_no_next_forced_change_in_authority(encoded_data: Vec<u8>, print_level: PrintLevel) {
+        write_assert_guards(
+            print_level,
+            format!("========= Verifying absence of next forced change of authority =========="),
+        );
+
+        let result = current_status::<Block, _>(decode_db(&encoded_data));
+        assert!(result.is_ok());
+        let status = result.unwrap();
+        assert!(status.possible_next_forced_change_in_authority.is_none());
+
+        write_success_assert(
+            print_level,
+            format!("Verified that forced change does not exists"),
+        );
+        write_assert_guards(
+            print_level,
+            format!("=========================================="),
+        );
+    }
+
     #[test]
     fn test_initialize_db_success() {
         let (encoded_data, initial_header) = assert_successful_db_init(None, 1);
@@ -574,14 +1346,16 @@ mod tests {
         let first_authority_set = LightAuthoritySet::construct_next_authority_set(
             &genesis_authority_set,
             first_voters.clone(),
-        );
+        )
+        .unwrap();
 
         let second_peers = [Ed25519Keyring::Eve, Ed25519Keyring::Ferdie];
         let second_voters = make_ids(&second_peers);
         let second_authority_set = LightAuthoritySet::construct_next_authority_set(
             &first_authority_set,
             second_voters.clone(),
-        );
+        )
+        .unwrap();
 
         write_test_flow(format!("Starting Authority set processing test"));
         let (encoded_data, initial_header) =
@@ -894,4 +1668,1643 @@ mod tests {
         assert_finalized_header(encoded_data.clone(), &fifth_header, 1);
         write_test_flow(format!("third, fourth and fifth headers are now finalized"));
     }
+
+    #[test]
+    fn test_retroactive_finalization() {
+        write_test_flow(format!("Starting retroactive finalization test"));
+        let peers = &[Ed25519Keyring::Alice];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(peers));
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set.clone()), 1);
+
+        write_test_flow(format!("\n\nIngesting three headers without justification"));
+        let first_header = create_next_header(initial_header.clone());
+        let second_header = create_next_header(first_header.clone());
+        let third_header = create_next_header(second_header.clone());
+        let fourth_header = create_next_header(third_header.clone());
+        let mut encoded_data = encoded_data;
+        for header in &[&first_header, &second_header, &third_header] {
+            encoded_data =
+                assert_successful_header_ingestion(encoded_data, (*header).clone(), None, 1);
+        }
+
+        let justification_for = |header: &Header, set_id: u64| {
+            let commit = create_justification_commit(1, set_id, vec![header.clone()], peers);
+            let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+                round: 1,
+                commit,
+                votes_ancestries: vec![],
+            };
+            grandpa_justification.encode()
+        };
+
+        write_test_flow(format!(
+            "\n\nJustification signed by other authority set isn't applied"
+        ));
+        let db = decode_db(&encoded_data);
+        let result = apply_justification::<Block, _>(db, justification_for(&second_header, 1));
+        assert!(result.is_err());
+
+        write_test_flow(format!(
+            "\n\nJustification for stored header finalizes it along with its ancestors"
+        ));
+        let db = decode_db(&encoded_data);
+        let result =
+            apply_justification::<Block, _>(db.clone(), justification_for(&second_header, 0));
+        assert_eq!(
+            result.unwrap(),
+            (*second_header.number(), second_header.hash())
+        );
+        let encoded_data = db.encode();
+        assert_finalized_header(encoded_data.clone(), &second_header, 1);
+        internal_assert_best_header(encoded_data.clone(), &third_header);
+
+        write_test_flow(format!("\n\nJustification can't be applied twice"));
+        let db = decode_db(&encoded_data);
+        let result = apply_justification::<Block, _>(db, justification_for(&second_header, 0));
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Header 3 is already finalized"
+        );
+
+        write_test_flow(format!(
+            "\n\nJustification for header which isn't stored can't be applied"
+        ));
+        let db = decode_db(&encoded_data);
+        let result = apply_justification::<Block, _>(db, justification_for(&fourth_header, 0));
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Header 5 finalized by justification is not stored"));
+
+        write_test_flow(format!(
+            "\n\nIngestion continues after retroactive finalization"
+        ));
+        assert_successful_header_ingestion(encoded_data, fourth_header, None, 1);
+    }
+
+    fn beefy_validator(seed: u8) -> (secp256k1::SecretKey, ecdsa::Public) {
+        let secret = secp256k1::SecretKey::parse(&[seed; 32]).unwrap();
+        let public = secp256k1::PublicKey::from_secret_key(&secret);
+        (
+            secret,
+            ecdsa::Public::from_raw(public.serialize_compressed()),
+        )
+    }
+
+    fn beefy_next_authority_set(id: u64, validators: &[ecdsa::Public]) -> BeefyNextAuthoritySet {
+        BeefyNextAuthoritySet {
+            id,
+            len: validators.len() as u32,
+            root: validators_merkle_root(validators),
+        }
+    }
+
+    fn beefy_finality_proof(
+        header: &Header,
+        validator_set_id: u64,
+        next_authority_set: BeefyNextAuthoritySet,
+        signers: &[Option<&secp256k1::SecretKey>],
+    ) -> BeefyFinalityProof<Block> {
+        let leaf = MmrLeaf::<Block> {
+            parent_number_and_hash: (*header.number(), header.hash()),
+            beefy_next_authority_set: next_authority_set,
+            parachain_heads: H256::zero(),
+        };
+        // Leaf is the second one in the MMR, so its proof consists of the first one
+        let first_leaf_hash = H256::repeat_byte(0xab);
+        let leaf_hash = keccak_256(&leaf.encode());
+        let commitment = Commitment::<Block> {
+            payload: H256(keccak_256(
+                &[first_leaf_hash.as_bytes(), &leaf_hash[..]].concat(),
+            )),
+            block_number: *header.number() + 1,
+            validator_set_id,
+        };
+        let message = secp256k1::Message::parse(&keccak_256(&commitment.encode()));
+        let signatures = signers
+            .iter()
+            .map(|possible_secret| {
+                possible_secret
+                    .map(|secret| ecdsa::Signature::from(secp256k1::sign(&message, secret)))
+            })
+            .collect();
+        BeefyFinalityProof {
+            signed_commitment: SignedCommitment {
+                commitment,
+                signatures,
+            },
+            leaf,
+            leaf_proof: MmrLeafProof {
+                leaf_index: 1,
+                leaf_count: 2,
+                items: vec![first_leaf_hash],
+            },
+        }
+    }
+
+    #[test]
+    fn test_beefy_finality_proofs() {
+        write_test_flow(format!("Starting BEEFY finality proof test"));
+        let (a_secret, a) = beefy_validator(1);
+        let (b_secret, b) = beefy_validator(2);
+        let (c_secret, c) = beefy_validator(3);
+        let (d_secret, d) = beefy_validator(4);
+        let (encoded_data, initial_header) = assert_successful_db_init(None, 1);
+
+        write_test_flow(format!("\n\nIngesting three headers without justification"));
+        let first_header = create_next_header(initial_header.clone());
+        let second_header = create_next_header(first_header.clone());
+        let third_header = create_next_header(second_header.clone());
+        let mut encoded_data = encoded_data;
+        for header in &[&first_header, &second_header, &third_header] {
+            encoded_data =
+                assert_successful_header_ingestion(encoded_data, (*header).clone(), None, 1);
+        }
+        let next_authority_set = beefy_next_authority_set(1, &[b.clone(), c.clone(), d.clone()]);
+        let signers = [Some(&a_secret), Some(&b_secret), Some(&c_secret), None];
+
+        write_test_flow(format!(
+            "\n\nFinality proof isn't applied until BEEFY validator set is stored"
+        ));
+        let proof = beefy_finality_proof(&first_header, 0, next_authority_set.clone(), &signers);
+        let result =
+            apply_beefy_finality_proof::<Block, _>(decode_db(&encoded_data), proof.encode(), None);
+        assert!(result.is_err());
+        let db = decode_db(&encoded_data);
+        assert!(initialize_beefy_validator_set(
+            db.clone(),
+            BeefyValidatorSet::new(0, vec![a.clone(), b.clone(), c.clone(), d.clone()]),
+        )
+        .is_ok());
+        let encoded_data = db.encode();
+
+        write_test_flow(format!(
+            "\n\nCommitment need to be signed by more than 2/3 of validators"
+        ));
+        let proof = beefy_finality_proof(
+            &first_header,
+            0,
+            next_authority_set.clone(),
+            &[Some(&a_secret), Some(&b_secret), None, None],
+        );
+        let result =
+            apply_beefy_finality_proof::<Block, _>(decode_db(&encoded_data), proof.encode(), None);
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Commitment is signed by 2 BEEFY validators, at least 3 are required"));
+        let proof = beefy_finality_proof(
+            &first_header,
+            0,
+            next_authority_set.clone(),
+            &[Some(&d_secret), Some(&b_secret), Some(&c_secret), None],
+        );
+        let result =
+            apply_beefy_finality_proof::<Block, _>(decode_db(&encoded_data), proof.encode(), None);
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Bad signature of BEEFY validator 0"));
+
+        write_test_flow(format!(
+            "\n\nMMR leaf need to be in the MMR signed by validators"
+        ));
+        let mut proof = beefy_finality_proof(&first_header, 0, next_authority_set.clone(), &[]);
+        proof.signed_commitment.commitment.payload = H256::zero();
+        let message =
+            secp256k1::Message::parse(&keccak_256(&proof.signed_commitment.commitment.encode()));
+        proof.signed_commitment.signatures = signers
+            .iter()
+            .map(|possible_secret| {
+                possible_secret
+                    .map(|secret| ecdsa::Signature::from(secp256k1::sign(&message, secret)))
+            })
+            .collect();
+        let result =
+            apply_beefy_finality_proof::<Block, _>(decode_db(&encoded_data), proof.encode(), None);
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("MMR leaf is not proven to be in the signed MMR"));
+
+        write_test_flow(format!(
+            "\n\nFinality proof finalizes header, and tracks the next validator set"
+        ));
+        let proof = beefy_finality_proof(&second_header, 0, next_authority_set.clone(), &signers);
+        let db = decode_db(&encoded_data);
+        let result = apply_beefy_finality_proof::<Block, _>(db.clone(), proof.encode(), None);
+        assert_eq!(
+            result.unwrap(),
+            (*second_header.number(), second_header.hash())
+        );
+        let encoded_data = db.encode();
+        assert_finalized_header(encoded_data.clone(), &second_header, 1);
+        let (possible_validator_set, possible_next_authority_set, possible_mmr_root) =
+            beefy_state::<Block, _>(decode_db(&encoded_data)).unwrap();
+        assert_eq!(possible_validator_set.unwrap().id, 0);
+        assert_eq!(possible_next_authority_set.unwrap(), next_authority_set);
+        let mmr_root = possible_mmr_root.unwrap();
+        assert_eq!(mmr_root.block_number, *second_header.number() + 1);
+        assert_eq!(mmr_root.root, proof.signed_commitment.commitment.payload);
+
+        write_test_flow(format!(
+            "\n\nNext validator set takes over once its validators are supplied"
+        ));
+        let proof = beefy_finality_proof(
+            &third_header,
+            1,
+            beefy_next_authority_set(2, &[b.clone(), c.clone(), d.clone()]),
+            &[Some(&b_secret), Some(&c_secret), Some(&d_secret)],
+        );
+        let result =
+            apply_beefy_finality_proof::<Block, _>(decode_db(&encoded_data), proof.encode(), None);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Validators of BEEFY validator set 1 need to be supplied"
+        );
+        let result = apply_beefy_finality_proof::<Block, _>(
+            decode_db(&encoded_data),
+            proof.encode(),
+            Some(vec![a.clone(), b.clone(), c.clone()]),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Supplied validators do not match BEEFY validator set 1 announced in MMR leaf"
+        );
+        let db = decode_db(&encoded_data);
+        let result = apply_beefy_finality_proof::<Block, _>(
+            db.clone(),
+            proof.encode(),
+            Some(vec![b.clone(), c.clone(), d.clone()]),
+        );
+        assert_eq!(
+            result.unwrap(),
+            (*third_header.number(), third_header.hash())
+        );
+        let encoded_data = db.encode();
+        assert_finalized_header(encoded_data.clone(), &third_header, 1);
+        let (possible_validator_set, possible_next_authority_set, _) =
+            beefy_state::<Block, _>(decode_db(&encoded_data)).unwrap();
+        assert_eq!(
+            possible_validator_set.unwrap(),
+            BeefyValidatorSet::new(1, vec![b.clone(), c.clone(), d.clone()])
+        );
+        assert_eq!(possible_next_authority_set.unwrap().id, 2);
+
+        write_test_flow(format!(
+            "\n\nCommitment signed by unknown validator set is rejected"
+        ));
+        let fourth_header = create_next_header(third_header.clone());
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, fourth_header.clone(), None, 1);
+        let proof = beefy_finality_proof(
+            &fourth_header,
+            5,
+            beefy_next_authority_set(6, &[b.clone()]),
+            &[Some(&b_secret)],
+        );
+        let result = apply_beefy_finality_proof::<Block, _>(
+            decode_db(&encoded_data),
+            proof.encode(),
+            Some(vec![b]),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "BEEFY validator set 5 is not known"
+        );
+    }
+
+    #[test]
+    fn test_verify_storage_proof() {
+        let mut db = MemoryDB::<BlakeTwo256>::default();
+        let mut state_root = H256::default();
+        {
+            let mut trie = TrieDBMut::<Layout<BlakeTwo256>>::new(&mut db, &mut state_root);
+            trie.insert(b"alice", b"100").unwrap();
+            trie.insert(b"bob", b"200").unwrap();
+        }
+        let proof: Vec<Vec<u8>> = db
+            .drain()
+            .into_iter()
+            .filter(|(_, (_, rc))| *rc > 0)
+            .map(|(_, (node, _))| node)
+            .collect();
+
+        let (encoded_data, initial_header) = assert_successful_db_init(None, 1);
+        let mut first_header = create_next_header(initial_header.clone());
+        first_header.state_root = state_root;
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, first_header.clone(), None, 1);
+        let second_header = create_next_header(first_header.clone());
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, second_header.clone(), None, 1);
+
+        // Header which isn't the best header can still be used
+        let result = verify_storage_proof::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.number,
+            b"bob",
+            Some(b"200"),
+            proof.clone(),
+        );
+        assert!(result.is_ok());
+        assert!(result.unwrap());
+
+        let result = verify_storage_proof::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.number,
+            b"bob",
+            Some(b"100"),
+            proof.clone(),
+        );
+        assert!(result.is_ok());
+        assert!(!result.unwrap());
+
+        let result = verify_storage_proof::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.number,
+            b"dave",
+            None,
+            proof.clone(),
+        );
+        assert!(result.is_ok());
+        assert!(result.unwrap());
+
+        // State root of initial header is different, so proof is incomplete for it
+        let result = verify_storage_proof::<Block, _>(
+            decode_db(&encoded_data),
+            initial_header.number,
+            b"bob",
+            Some(b"200"),
+            proof.clone(),
+        );
+        assert!(result.is_err());
+
+        // Header from future
+        let result = verify_storage_proof::<Block, _>(
+            decode_db(&encoded_data),
+            second_header.number + 1,
+            b"bob",
+            Some(b"200"),
+            proof.clone(),
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "UnknownBlock: Header at height 4 is not stored"
+        );
+    }
+
+    #[test]
+    fn test_forced_authority_set_change() {
+        let genesis_peers = [Ed25519Keyring::Alice, Ed25519Keyring::Bob];
+        let genesis_voters = make_ids(&genesis_peers);
+        let genesis_authority_set = LightAuthoritySet::new(0, genesis_voters.clone());
+
+        let forced_peers = [Ed25519Keyring::Charlie, Ed25519Keyring::Dave];
+        let forced_voters = make_ids(&forced_peers);
+        let forced_authority_set = LightAuthoritySet::construct_next_authority_set(
+            &genesis_authority_set,
+            forced_voters.clone(),
+        )
+        .unwrap();
+
+        write_test_flow(format!("Starting forced authority set change test"));
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set.clone()), 1);
+
+        let mut first_header = create_next_header(initial_header.clone());
+        let change = ScheduledChange {
+            next_authorities: forced_voters.clone(),
+            delay: 2,
+        };
+        first_header.digest_mut().push(DigestItem::Consensus(
+            GRANDPA_ENGINE_ID,
+            sp_finality_grandpa::ConsensusLog::ForcedChange(initial_header.number, change.clone())
+                .encode(),
+        ));
+        let mut second_header = create_next_header(first_header.clone());
+        let third_header = create_next_header(second_header.clone());
+        let fourth_header = create_next_header(third_header.clone());
+
+        write_test_flow(format!(
+            "\n\nPushing forced change with next header and verifying data."
+        ));
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, first_header.clone(), None, 1);
+        assert_next_forced_change_in_authority(
+            encoded_data.clone(),
+            &change,
+            initial_header.number,
+            1,
+        );
+        assert_no_next_change_in_authority(encoded_data.clone(), 1);
+        assert_authority_set(encoded_data.clone(), &genesis_authority_set, 1);
+
+        write_test_flow(format!(
+            "\n\nWe cannot push another forced change while previous one exists"
+        ));
+        second_header.digest_mut().push(DigestItem::Consensus(
+            GRANDPA_ENGINE_ID,
+            sp_finality_grandpa::ConsensusLog::ForcedChange(
+                initial_header.number,
+                ScheduledChange {
+                    next_authorities: genesis_voters.clone(),
+                    delay: 1,
+                },
+            )
+            .encode(),
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            second_header.clone(),
+            None,
+            String::from("VerificationFailed(\"Forced change already exists.\")"),
+            1,
+        );
+        second_header.digest.clear();
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, second_header.clone(), None, 1);
+
+        write_test_flow(format!(
+            "\n\nForced change is enacted even though block signalling it is never finalized"
+        ));
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, third_header.clone(), None, 1);
+        assert_no_next_forced_change_in_authority(encoded_data.clone(), 1);
+        assert_authority_set(encoded_data.clone(), &forced_authority_set, 1);
+
+        write_test_flow(format!(
+            "\n\nJustification signed by previous authority set is no longer accepted"
+        ));
+        let commit = create_justification_commit(1, 0, vec![fourth_header.clone()], &genesis_peers);
+        let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+            round: 1,
+            commit,
+            votes_ancestries: vec![],
+        };
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            fourth_header.clone(),
+            Some(grandpa_justification.encode()),
+            String::from("Error: Justification is invalid or authority set is not updated."),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nJustification signed by forced authority set finalizes the header"
+        ));
+        let commit = create_justification_commit(1, 1, vec![fourth_header.clone()], &forced_peers);
+        let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+            round: 1,
+            commit,
+            votes_ancestries: vec![],
+        };
+        let encoded_data = assert_successful_header_ingestion(
+            encoded_data,
+            fourth_header.clone(),
+            Some(grandpa_justification.encode()),
+            1,
+        );
+        assert_finalized_header(encoded_data.clone(), &fourth_header, 1);
+    }
+
+    #[test]
+    fn test_forced_authority_set_change_with_pending_scheduled_change() {
+        let genesis_peers = [Ed25519Keyring::Alice, Ed25519Keyring::Bob];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(&genesis_peers));
+        let scheduled_voters = make_ids(&[Ed25519Keyring::Charlie, Ed25519Keyring::Dave]);
+        let forced_voters = make_ids(&[Ed25519Keyring::Eve, Ed25519Keyring::Ferdie]);
+        let forced_authority_set = LightAuthoritySet::construct_next_authority_set(
+            &genesis_authority_set,
+            forced_voters.clone(),
+        )
+        .unwrap();
+
+        write_test_flow(format!(
+            "Starting forced authority set change with pending scheduled change test"
+        ));
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set.clone()), 1);
+
+        // Scheduled change will be due at block number 7
+        let mut first_header = create_next_header(initial_header.clone());
+        let scheduled_change = ScheduledChange {
+            next_authorities: scheduled_voters.clone(),
+            delay: 5,
+        };
+        first_header.digest_mut().push(DigestItem::Consensus(
+            GRANDPA_ENGINE_ID,
+            sp_finality_grandpa::ConsensusLog::ScheduledChange(scheduled_change.clone()).encode(),
+        ));
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, first_header.clone(), None, 1);
+
+        let forced_change = ScheduledChange {
+            next_authorities: forced_voters.clone(),
+            delay: 1,
+        };
+        let create_forced_header = |median_last_finalized: NumberFor<Block>| {
+            let mut header = create_next_header(first_header.clone());
+            header.digest_mut().push(DigestItem::Consensus(
+                GRANDPA_ENGINE_ID,
+                sp_finality_grandpa::ConsensusLog::ForcedChange(
+                    median_last_finalized,
+                    forced_change.clone(),
+                )
+                .encode(),
+            ));
+            header
+        };
+
+        write_test_flow(format!(
+            "\n\nForced change claiming scheduled change to be finalized can't be enacted"
+        ));
+        let second_header = create_forced_header(scheduled_change.delay + 2);
+        let encoded_data_with_dependency = assert_successful_header_ingestion(
+            encoded_data.clone(),
+            second_header.clone(),
+            None,
+            1,
+        );
+        assert_next_change_in_authority(encoded_data_with_dependency.clone(), &scheduled_change, 1);
+        assert_failed_header_ingestion(
+            encoded_data_with_dependency,
+            create_next_header(second_header),
+            None,
+            String::from(
+                "VerificationFailed(\"Forced change depends on unapplied scheduled change.\")",
+            ),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nOtherwise forced change is enacted and scheduled change is discarded"
+        ));
+        let second_header = create_forced_header(first_header.number);
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, second_header.clone(), None, 1);
+        let encoded_data = assert_successful_header_ingestion(
+            encoded_data,
+            create_next_header(second_header),
+            None,
+            1,
+        );
+        assert_no_next_change_in_authority(encoded_data.clone(), 1);
+        assert_no_next_forced_change_in_authority(encoded_data.clone(), 1);
+        assert_authority_set(encoded_data.clone(), &forced_authority_set, 1);
+    }
+
+    #[test]
+    fn test_batch_header_ingestion() {
+        write_test_flow(format!("Starting batch header ingestion test"));
+        let peers = &[Ed25519Keyring::Alice];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(peers));
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set.clone()), 1);
+
+        let first_header = create_next_header(initial_header.clone());
+        let second_header = create_next_header(first_header.clone());
+        let third_header = create_next_header(second_header.clone());
+        let fourth_header = create_next_header(third_header.clone());
+        let fifth_header = create_next_header(fourth_header.clone());
+        let sixth_header = create_next_header(fifth_header.clone());
+        let seventh_header = create_next_header(sixth_header.clone());
+
+        let commit = create_justification_commit(1, 0, vec![third_header.clone()], peers);
+        let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+            round: 1,
+            commit,
+            votes_ancestries: vec![],
+        };
+
+        write_test_flow(format!(
+            "\n\nIngesting four headers in one batch, third one carrying justification"
+        ));
+        let db = decode_db(&encoded_data);
+        let result = ingest_finalized_headers::<Block, _>(
+            db.clone(),
+            vec![
+                (first_header.clone(), None),
+                (second_header.clone(), None),
+                (third_header.clone(), Some(grandpa_justification.encode())),
+                (fourth_header.clone(), None),
+            ],
+            256,
+        );
+        assert!(result.is_ok());
+        let block_import_responses = result.unwrap();
+        let encoded_data = db.encode();
+        assert_eq!(block_import_responses.len(), 4);
+        internal_assert_best_header(encoded_data.clone(), &fourth_header);
+        assert_finalized_header(encoded_data.clone(), &third_header, 1);
+
+        write_test_flow(format!(
+            "\n\nBatch fails as a whole if any of the headers can't be ingested"
+        ));
+        let result = ingest_finalized_headers::<Block, _>(
+            decode_db(&encoded_data),
+            vec![(fifth_header.clone(), None), (seventh_header.clone(), None)],
+            256,
+        );
+        assert!(result.is_err());
+        assert_eq!(result.err().unwrap(), "Header 8: UnknownParent");
+        // Previous state is still usable and unaffected
+        internal_assert_best_header(encoded_data.clone(), &fourth_header);
+        assert_successful_header_ingestion(encoded_data, fifth_header, None, 1);
+    }
+
+    #[test]
+    fn test_skip_ahead_header_ingestion() {
+        write_test_flow(format!("Starting skip ahead header ingestion test"));
+        let genesis_peers = &[Ed25519Keyring::Alice];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(genesis_peers));
+        let first_peers = &[Ed25519Keyring::Bob];
+        let first_authority_set = LightAuthoritySet::construct_next_authority_set(
+            &genesis_authority_set,
+            make_ids(first_peers),
+        )
+        .unwrap();
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set.clone()), 1);
+
+        let mut first_header = create_next_header(initial_header.clone());
+        let change = ScheduledChange {
+            next_authorities: make_ids(first_peers),
+            delay: 2,
+        };
+        first_header.digest_mut().push(DigestItem::Consensus(
+            GRANDPA_ENGINE_ID,
+            sp_finality_grandpa::ConsensusLog::ScheduledChange(change.clone()).encode(),
+        ));
+        let second_header = create_next_header(first_header.clone());
+        let third_header = create_next_header(second_header.clone());
+        let fourth_header = create_next_header(third_header.clone());
+        let fifth_header = create_next_header(fourth_header.clone());
+        let sixth_header = create_next_header(fifth_header.clone());
+        let seventh_header = create_next_header(sixth_header.clone());
+        let eighth_header = create_next_header(seventh_header.clone());
+
+        let justification_for = |header: &Header, set_id: u64, peers: &[Ed25519Keyring]| {
+            let commit = create_justification_commit(1, set_id, vec![header.clone()], peers);
+            let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+                round: 1,
+                commit,
+                votes_ancestries: vec![],
+            };
+            grandpa_justification.encode()
+        };
+
+        write_test_flow(format!(
+            "\n\nHeader skipping ahead is rejected by regular ingestion"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            third_header.clone(),
+            Some(justification_for(&third_header, 0, genesis_peers)),
+            String::from("UnknownParent"),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nIngesting finalized header signalling scheduled change"
+        ));
+        let encoded_data = assert_successful_header_ingestion(
+            encoded_data,
+            first_header.clone(),
+            Some(justification_for(&first_header, 0, genesis_peers)),
+            1,
+        );
+        assert_finalized_header(encoded_data.clone(), &first_header, 1);
+        assert_next_change_in_authority(encoded_data.clone(), &change, 1);
+
+        write_test_flow(format!(
+            "\n\nHeader enacting scheduled change cannot be skipped"
+        ));
+        let result = ingest_justified_header::<Block, _>(
+            decode_db(&encoded_data),
+            seventh_header.clone(),
+            justification_for(&seventh_header, 0, genesis_peers),
+            256,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap(),
+            "VerificationFailed(\"Header enacting pending authority set change need to be ingested first.\")"
+        );
+
+        write_test_flow(format!(
+            "\n\nIngesting header enacting scheduled change and a header which is not finalized"
+        ));
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, second_header.clone(), None, 1);
+        let encoded_data = assert_successful_header_ingestion(
+            encoded_data,
+            third_header.clone(),
+            Some(justification_for(&third_header, 1, first_peers)),
+            1,
+        );
+        assert_authority_set(encoded_data.clone(), &first_authority_set, 1);
+        assert_finalized_header(encoded_data.clone(), &third_header, 1);
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, fourth_header.clone(), None, 1);
+
+        write_test_flow(format!(
+            "\n\nHeader skipping ahead needs valid justification of current authority set"
+        ));
+        let result = ingest_justified_header::<Block, _>(
+            decode_db(&encoded_data),
+            seventh_header.clone(),
+            justification_for(&seventh_header, 0, genesis_peers),
+            256,
+        );
+        assert!(result.is_err());
+
+        write_test_flow(format!(
+            "\n\nSkipping ahead to justified header, discarding header which is not finalized"
+        ));
+        let db = decode_db(&encoded_data);
+        let result = ingest_justified_header::<Block, _>(
+            db.clone(),
+            seventh_header.clone(),
+            justification_for(&seventh_header, 1, first_peers),
+            256,
+        );
+        assert!(result.is_ok());
+        let encoded_data = db.encode();
+        internal_assert_best_header(encoded_data.clone(), &seventh_header);
+        assert_finalized_header(encoded_data.clone(), &seventh_header, 1);
+        let status = current_status::<Block, _>(decode_db(&encoded_data)).unwrap();
+        assert_eq!(status.gaps, vec![(5, 7)]);
+        assert!(header_by_number::<Block, _>(decode_db(&encoded_data), 5)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            header_by_number::<Block, _>(decode_db(&encoded_data), 4).unwrap(),
+            Some(third_header.clone())
+        );
+
+        write_test_flow(format!("\n\nListing stored headers around the gap"));
+        assert_eq!(
+            headers_in_range::<Block, _>(decode_db(&encoded_data), 0, 100, 10).unwrap(),
+            vec![
+                initial_header.clone(),
+                first_header.clone(),
+                second_header.clone(),
+                third_header.clone(),
+                seventh_header.clone()
+            ]
+        );
+        assert_eq!(
+            headers_in_range::<Block, _>(decode_db(&encoded_data), 3, 100, 2).unwrap(),
+            vec![second_header.clone(), third_header.clone()]
+        );
+        assert!(
+            headers_in_range::<Block, _>(decode_db(&encoded_data), 5, 7, 10)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            headers_in_range::<Block, _>(decode_db(&encoded_data), 6, 100, 10).unwrap(),
+            vec![seventh_header.clone()]
+        );
+        assert_eq!(
+            header_by_hash::<Block, _>(decode_db(&encoded_data), seventh_header.hash()).unwrap(),
+            Some(seventh_header.clone())
+        );
+        assert!(
+            header_by_hash::<Block, _>(decode_db(&encoded_data), fourth_header.hash())
+                .unwrap()
+                .is_none()
+        );
+        let storage_info = stored_headers_info::<Block, _>(decode_db(&encoded_data)).unwrap();
+        assert_eq!(storage_info.total_stored, 5);
+        assert_eq!(storage_info.oldest_stored_hash, initial_header.hash());
+
+        write_test_flow(format!(
+            "\n\nHeaders following skipped one are ingested as usual"
+        ));
+        assert_successful_header_ingestion(encoded_data, eighth_header, None, 1);
+    }
+
+    #[test]
+    fn test_warp_sync_proof() {
+        write_test_flow(format!("Starting warp sync proof test"));
+        let genesis_peers = &[Ed25519Keyring::Alice];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(genesis_peers));
+        let first_peers = &[Ed25519Keyring::Bob];
+        let second_peers = &[Ed25519Keyring::Charlie];
+        let second_authority_set = LightAuthoritySet::new(2, make_ids(second_peers));
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set), 1);
+
+        let with_change = |mut header: Header, peers: &[Ed25519Keyring], delay: u32| {
+            header.digest_mut().push(DigestItem::Consensus(
+                GRANDPA_ENGINE_ID,
+                sp_finality_grandpa::ConsensusLog::ScheduledChange(ScheduledChange {
+                    next_authorities: make_ids(peers),
+                    delay,
+                })
+                .encode(),
+            ));
+            header
+        };
+        let mut headers = vec![initial_header];
+        for number in 2..=10 {
+            let mut header = create_next_header(headers.last().unwrap().clone());
+            if number == 4 {
+                header = with_change(header, first_peers, 0);
+            } else if number == 8 {
+                header = with_change(header, second_peers, 0);
+            }
+            headers.push(header);
+        }
+        let header_at = |number: usize| headers[number - 1].clone();
+
+        let fragment_for = |header: Header, set_id: u64, peers: &[Ed25519Keyring]| {
+            let commit = create_justification_commit(1, set_id, vec![header.clone()], peers);
+            WarpSyncFragment::<Block> {
+                header,
+                justification: GrandpaJustification {
+                    round: 1,
+                    commit,
+                    votes_ancestries: vec![],
+                },
+            }
+        };
+        let apply_proof =
+            |encoded_data: &[u8], proofs: Vec<WarpSyncFragment<Block>>, is_finished: bool| {
+                let db = decode_db(encoded_data);
+                let warp_sync_proof = WarpSyncProof::<Block> {
+                    proofs,
+                    is_finished,
+                };
+                apply_warp_sync_proof::<Block, _>(db.clone(), warp_sync_proof.encode(), 256)
+                    .map(|(header, _)| (header, db.encode()))
+            };
+
+        write_test_flow(format!("\n\nWarp sync proof need to have fragments"));
+        assert_eq!(
+            apply_proof(&encoded_data, vec![], true).err().unwrap(),
+            "Warp sync proof need to have at least one fragment"
+        );
+
+        write_test_flow(format!(
+            "\n\nFragment need to be finalized by authority set scheduled by the previous one"
+        ));
+        let result = apply_proof(
+            &encoded_data,
+            vec![
+                fragment_for(header_at(4), 0, genesis_peers),
+                fragment_for(header_at(8), 0, genesis_peers),
+            ],
+            false,
+        );
+        assert!(result
+            .err()
+            .unwrap()
+            .starts_with("Fragment header 8: bad justification for header"));
+
+        write_test_flow(format!(
+            "\n\nFragments need to schedule authority set change, unless proof is finished"
+        ));
+        assert_eq!(
+            apply_proof(
+                &encoded_data,
+                vec![fragment_for(header_at(3), 0, genesis_peers)],
+                false
+            )
+            .err()
+            .unwrap(),
+            "Fragment header 3 is missing authority set change"
+        );
+        let delayed_change_header = with_change(header_at(4), first_peers, 1);
+        assert_eq!(
+            apply_proof(
+                &encoded_data,
+                vec![fragment_for(delayed_change_header, 0, genesis_peers)],
+                false
+            )
+            .err()
+            .unwrap(),
+            "Authority set change scheduled by fragment header 4 need to be enacted at it"
+        );
+        assert_eq!(
+            apply_proof(
+                &encoded_data,
+                vec![
+                    fragment_for(header_at(8), 0, genesis_peers),
+                    fragment_for(header_at(4), 1, first_peers),
+                ],
+                false
+            )
+            .err()
+            .unwrap(),
+            "Fragment header 4 does not follow fragment header 8"
+        );
+
+        write_test_flow(format!(
+            "\n\nProof ending with authority set change enacts it at the last fragment"
+        ));
+        let (header, synced_data) = apply_proof(
+            &encoded_data,
+            vec![
+                fragment_for(header_at(4), 0, genesis_peers),
+                fragment_for(header_at(8), 1, first_peers),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(header, header_at(8));
+        assert_finalized_header(synced_data.clone(), &header_at(8), 1);
+        assert_authority_set(synced_data.clone(), &second_authority_set, 1);
+        let status = current_status::<Block, _>(decode_db(&synced_data)).unwrap();
+        assert!(status.possible_next_change_in_authority.is_none());
+        assert_eq!(status.gaps, vec![(2, 7)]);
+        assert_successful_header_ingestion(
+            synced_data,
+            header_at(9),
+            Some(
+                fragment_for(header_at(9), 2, second_peers)
+                    .justification
+                    .encode(),
+            ),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nFinished proof moves authority set forward and ingests its target header"
+        ));
+        let proofs = || {
+            vec![
+                fragment_for(header_at(4), 0, genesis_peers),
+                fragment_for(header_at(8), 1, first_peers),
+                fragment_for(header_at(10), 2, second_peers),
+            ]
+        };
+        let (header, synced_data) = apply_proof(&encoded_data, proofs(), true).unwrap();
+        assert_eq!(header, header_at(10));
+        internal_assert_best_header(synced_data.clone(), &header_at(10));
+        assert_finalized_header(synced_data.clone(), &header_at(10), 1);
+        assert_authority_set(synced_data.clone(), &second_authority_set, 1);
+
+        write_test_flow(format!(
+            "\n\nFinalized headers cannot be warp synced to again"
+        ));
+        assert_eq!(
+            apply_proof(&synced_data, proofs(), true).err().unwrap(),
+            "Header 4 is already finalized"
+        );
+    }
+
+    #[test]
+    fn test_u64_keccak_header_ingestion() {
+        write_test_flow(format!(
+            "Starting header ingestion test with u64 block numbers and Keccak256 hashing"
+        ));
+        let peers = &[Ed25519Keyring::Alice];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(peers));
+        // Block number beyond u32 range
+        let initial_header = U64KeccakHeader::new(
+            u64::from(u32::max_value()) + 1,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let db = create(NUM_COLUMNS);
+        let result = initialize_state::<U64KeccakBlock, _>(
+            db.clone(),
+            initial_header.clone(),
+            genesis_authority_set,
+            PruningPolicy::default(),
+            2,
+        );
+        assert!(result.is_ok());
+
+        let mut first_header = initial_header.clone();
+        first_header.number += 1;
+        first_header.parent_hash = initial_header.hash();
+
+        let precommit = Precommit::<U64KeccakBlock> {
+            target_hash: first_header.hash(),
+            target_number: first_header.number,
+        };
+        let msg = Message::<U64KeccakBlock>::Precommit(precommit.clone());
+        let encoded_msg = (&msg, 1u64, 0u64).encode();
+        let commit = Commit::<U64KeccakBlock> {
+            target_hash: first_header.hash(),
+            target_number: first_header.number,
+            precommits: vec![SignedPrecommit {
+                precommit,
+                signature: Ed25519Keyring::Alice.sign(&encoded_msg[..]).into(),
+                id: Ed25519Keyring::Alice.public().into(),
+            }],
+        };
+        let grandpa_justification: GrandpaJustification<U64KeccakBlock> = GrandpaJustification {
+            round: 1,
+            commit,
+            votes_ancestries: vec![],
+        };
+
+        write_test_flow(format!("\n\nIngesting header with justification"));
+        let result = ingest_finalized_header::<U64KeccakBlock, _>(
+            db.clone(),
+            first_header.clone(),
+            Some(grandpa_justification.encode()),
+            256,
+        );
+        assert!(result.is_ok());
+        let encoded_data = db.encode();
+        let status = current_status::<U64KeccakBlock, _>(decode_db(&encoded_data)).unwrap();
+        assert_eq!(status.possible_best_header, Some(first_header.clone()));
+        assert_eq!(
+            status.possible_last_finalized_header,
+            Some(first_header.clone())
+        );
+        assert_eq!(
+            header_by_number::<U64KeccakBlock, _>(
+                decode_db(&encoded_data),
+                u64::from(u32::max_value()) + 2
+            )
+            .unwrap(),
+            Some(first_header)
+        );
+    }
+
+    #[test]
+    fn test_misbehaviour_verification() {
+        write_test_flow(format!("Starting misbehaviour verification test"));
+        let peers = &[Ed25519Keyring::Alice];
+        let genesis_authority_set = LightAuthoritySet::new(0, make_ids(peers));
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(genesis_authority_set.clone()), 1);
+
+        let first_header = create_next_header(initial_header.clone());
+        let mut conflicting_first_header = first_header.clone();
+        conflicting_first_header.state_root = H256::repeat_byte(1);
+        let second_header = create_next_header(first_header.clone());
+
+        let justification_for = |header: &Header| {
+            let commit = create_justification_commit(1, 0, vec![header.clone()], peers);
+            let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+                round: 1,
+                commit,
+                votes_ancestries: vec![],
+            };
+            grandpa_justification.encode()
+        };
+
+        write_test_flow(format!(
+            "\n\nTwo different headers at the same height finalized by the same authority set"
+        ));
+        let result = verify_misbehaviour::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.clone(),
+            justification_for(&first_header),
+            conflicting_first_header.clone(),
+            justification_for(&conflicting_first_header),
+        );
+        assert!(result.is_ok());
+
+        write_test_flow(format!("\n\nSame header twice isn't a misbehaviour"));
+        let result = verify_misbehaviour::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.clone(),
+            justification_for(&first_header),
+            first_header.clone(),
+            justification_for(&first_header),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Headers need to be different to prove misbehaviour"
+        );
+
+        write_test_flow(format!(
+            "\n\nHeaders at different heights aren't a misbehaviour"
+        ));
+        let result = verify_misbehaviour::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.clone(),
+            justification_for(&first_header),
+            second_header.clone(),
+            justification_for(&second_header),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Headers need to be at the same height. Got: 2 and 3"
+        );
+
+        write_test_flow(format!(
+            "\n\nBoth headers need to be finalized by their justifications"
+        ));
+        let result = verify_misbehaviour::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.clone(),
+            justification_for(&first_header),
+            conflicting_first_header,
+            justification_for(&first_header),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "bad justification for header: invalid commit target in grandpa justification"
+        );
+    }
+
+    #[test]
+    fn test_misbehaviour_verification_with_empty_authority_set() {
+        write_test_flow(format!(
+            "Starting misbehaviour verification test with empty authority set"
+        ));
+        let peers = &[Ed25519Keyring::Alice];
+        let (encoded_data, initial_header) = assert_successful_db_init(None, 1);
+
+        let first_header = create_next_header(initial_header.clone());
+        let mut conflicting_first_header = first_header.clone();
+        conflicting_first_header.state_root = H256::repeat_byte(1);
+
+        let justification_for = |header: &Header| {
+            let commit = create_justification_commit(1, 0, vec![header.clone()], peers);
+            let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+                round: 1,
+                commit,
+                votes_ancestries: vec![],
+            };
+            grandpa_justification.encode()
+        };
+
+        write_test_flow(format!(
+            "\n\nJustifications can't be checked against an authority set without voters"
+        ));
+        let result = verify_misbehaviour::<Block, _>(
+            decode_db(&encoded_data),
+            first_header.clone(),
+            justification_for(&first_header),
+            conflicting_first_header.clone(),
+            justification_for(&conflicting_first_header),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Current state of blockchain has invalid authorities set"
+        );
+    }
+
+    fn babe_epoch(epoch_index: u64, start_slot: u64, pairs: &[&sr25519::Pair]) -> BabeEpoch {
+        BabeEpoch {
+            epoch_index,
+            start_slot,
+            duration: 10,
+            authorities: pairs.iter().map(|pair| (pair.public(), 1)).collect(),
+            randomness: [epoch_index as u8; 32],
+            c: (1, 4),
+            secondary_slots: true,
+        }
+    }
+
+    fn create_babe_header(header: Header, pre_digest: PreDigest) -> Header {
+        let mut next_header = create_next_header(header);
+        next_header
+            .digest_mut()
+            .push(DigestItem::PreRuntime(BABE_ENGINE_ID, pre_digest.encode()));
+        next_header
+    }
+
+    fn seal_babe_header(mut header: Header, pair: &sr25519::Pair) -> Header {
+        let signature = pair.sign(header.hash().as_ref());
+        header
+            .digest_mut()
+            .push(DigestItem::Seal(BABE_ENGINE_ID, signature.encode()));
+        header
+    }
+
+    fn secondary_pre_digest(slot_number: u64) -> PreDigest {
+        PreDigest::Secondary(SecondaryPreDigest {
+            authority_index: 0,
+            slot_number,
+        })
+    }
+
+    fn primary_pre_digest(
+        seed: &[u8; 32],
+        epoch: &BabeEpoch,
+        slot_number: u64,
+        claimed_slot_number: u64,
+    ) -> PreDigest {
+        let keypair = MiniSecretKey::from_bytes(seed)
+            .unwrap()
+            .expand_to_keypair(ExpansionMode::Ed25519);
+        let transcript = make_transcript(&epoch.randomness, slot_number, epoch.epoch_index);
+        let (inout, proof, _) = keypair.vrf_sign(transcript);
+        PreDigest::Primary(PrimaryPreDigest {
+            authority_index: 0,
+            slot_number: claimed_slot_number,
+            vrf_output: inout.to_output().to_bytes(),
+            vrf_proof: proof.to_bytes(),
+        })
+    }
+
+    fn announce_babe_epoch(mut header: Header, pairs: &[&sr25519::Pair]) -> Header {
+        let descriptor = NextEpochDescriptor {
+            authorities: pairs.iter().map(|pair| (pair.public(), 1)).collect(),
+            randomness: [0xff; 32],
+        };
+        header.digest_mut().push(DigestItem::Consensus(
+            BABE_ENGINE_ID,
+            BabeConsensusLog::NextEpochData(descriptor).encode(),
+        ));
+        header
+    }
+
+    #[test]
+    fn test_babe_seal_verification() {
+        write_test_flow(format!("Starting BABE seal verification test"));
+        let peers = &[Ed25519Keyring::Alice];
+        let alice = sr25519::Pair::from_seed(&[1; 32]);
+        let bob = sr25519::Pair::from_seed(&[2; 32]);
+        let (encoded_data, initial_header) =
+            assert_successful_db_init(Some(LightAuthoritySet::new(0, make_ids(peers))), 1);
+        let db = decode_db(&encoded_data);
+        assert!(initialize_babe_epochs(
+            db.clone(),
+            babe_epoch(0, 0, &[&alice]),
+            Some(babe_epoch(1, 10, &[&alice])),
+        )
+        .is_ok());
+        let encoded_data = db.encode();
+
+        write_test_flow(format!(
+            "\n\nHeaders without justification need to carry slot claim and seal"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            create_next_header(initial_header.clone()),
+            None,
+            String::from("VerificationFailed(\"Header has no BABE pre-runtime digest\")"),
+            1,
+        );
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            create_babe_header(initial_header.clone(), secondary_pre_digest(1)),
+            None,
+            String::from("VerificationFailed(\"Header has bad BABE seal\")"),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nHeader sealed by authority not assigned to the slot is rejected"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(initial_header.clone(), secondary_pre_digest(1)),
+                &bob,
+            ),
+            None,
+            String::from("VerificationFailed(\"Bad signature on BABE seal\")"),
+            1,
+        );
+
+        write_test_flow(format!("\n\nHeader sealed by slot author is ingested"));
+        let first_header = seal_babe_header(
+            create_babe_header(initial_header.clone(), secondary_pre_digest(1)),
+            &alice,
+        );
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, first_header.clone(), None, 1);
+
+        write_test_flow(format!(
+            "\n\nSlot outside of known epochs is rejected without justification"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(first_header.clone(), secondary_pre_digest(25)),
+                &alice,
+            ),
+            None,
+            String::from(
+                "VerificationFailed(\"Slot 25 of header is outside of known BABE epochs\")",
+            ),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nHeader starting next epoch need to announce the one after it"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(first_header.clone(), secondary_pre_digest(10)),
+                &alice,
+            ),
+            None,
+            String::from(
+                "VerificationFailed(\"Header starting BABE epoch need to announce the next one\")",
+            ),
+            1,
+        );
+        let second_header = seal_babe_header(
+            announce_babe_epoch(
+                create_babe_header(first_header.clone(), secondary_pre_digest(10)),
+                &[&bob],
+            ),
+            &alice,
+        );
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, second_header.clone(), None, 1);
+        let (possible_epoch, possible_next_epoch) = babe_epochs(decode_db(&encoded_data)).unwrap();
+        assert_eq!(possible_epoch.unwrap(), babe_epoch(1, 10, &[&alice]));
+        let next_epoch = possible_next_epoch.unwrap();
+        assert_eq!(next_epoch.epoch_index, 2);
+        assert_eq!(next_epoch.start_slot, 20);
+        assert_eq!(next_epoch.authorities, vec![(bob.public(), 1)]);
+
+        write_test_flow(format!(
+            "\n\nHeader claiming a slot not following slot of its parent is rejected"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(second_header.clone(), secondary_pre_digest(10)),
+                &alice,
+            ),
+            None,
+            String::from(
+                "VerificationFailed(\"Slot 10 of header does not follow slot 10 of its parent\")",
+            ),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nJustified header is accepted outside of known epochs, and tracks epoch it announces"
+        ));
+        let third_header = announce_babe_epoch(
+            create_babe_header(second_header.clone(), secondary_pre_digest(35)),
+            &[&alice],
+        );
+        let commit = create_justification_commit(1, 0, vec![third_header.clone()], peers);
+        let grandpa_justification: GrandpaJustification<Block> = GrandpaJustification {
+            round: 1,
+            commit,
+            votes_ancestries: vec![],
+        };
+        let encoded_data = assert_successful_header_ingestion(
+            encoded_data,
+            third_header.clone(),
+            Some(grandpa_justification.encode()),
+            1,
+        );
+        let (possible_epoch, possible_next_epoch) = babe_epochs(decode_db(&encoded_data)).unwrap();
+        assert_eq!(possible_epoch.unwrap().epoch_index, 1);
+        let next_epoch = possible_next_epoch.unwrap();
+        assert_eq!(next_epoch.epoch_index, 4);
+        assert_eq!(next_epoch.start_slot, 40);
+        assert_eq!(next_epoch.authorities, vec![(alice.public(), 1)]);
+    }
+
+    #[test]
+    fn test_babe_primary_slot_claims() {
+        write_test_flow(format!("Starting BABE primary slot claim test"));
+        let alice_seed = [1; 32];
+        let alice = sr25519::Pair::from_seed(&alice_seed);
+        let (encoded_data, initial_header) = assert_successful_db_init(None, 1);
+        // Every primary claim wins the slot when it can not be empty
+        let epoch = BabeEpoch {
+            c: (1, 1),
+            secondary_slots: false,
+            ..babe_epoch(0, 0, &[&alice])
+        };
+        let db = decode_db(&encoded_data);
+        assert!(initialize_babe_epochs(db.clone(), epoch.clone(), None).is_ok());
+        let encoded_data = db.encode();
+
+        write_test_flow(format!(
+            "\n\nPrimary claim with valid VRF proof is ingested"
+        ));
+        let first_header = seal_babe_header(
+            create_babe_header(
+                initial_header.clone(),
+                primary_pre_digest(&alice_seed, &epoch, 1, 1),
+            ),
+            &alice,
+        );
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, first_header.clone(), None, 1);
+
+        write_test_flow(format!("\n\nSecondary claim is rejected once disabled"));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(first_header.clone(), secondary_pre_digest(2)),
+                &alice,
+            ),
+            None,
+            String::from("VerificationFailed(\"Secondary slots are not allowed in BABE epoch\")"),
+            1,
+        );
+
+        write_test_flow(format!("\n\nVRF proof for another slot is rejected"));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(
+                    first_header.clone(),
+                    primary_pre_digest(&alice_seed, &epoch, 3, 2),
+                ),
+                &alice,
+            ),
+            None,
+            String::from("VerificationFailed(\"VRF verification of primary slot claim failed\")"),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nPrimary claim is rejected when VRF output is over the threshold"
+        ));
+        // No slot can be won when every slot is meant to be empty
+        let epoch = BabeEpoch { c: (0, 1), ..epoch };
+        let db = decode_db(&encoded_data);
+        assert!(initialize_babe_epochs(db.clone(), epoch.clone(), None).is_ok());
+        let encoded_data = db.encode();
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_babe_header(
+                create_babe_header(
+                    first_header.clone(),
+                    primary_pre_digest(&alice_seed, &epoch, 2, 2),
+                ),
+                &alice,
+            ),
+            None,
+            String::from(
+                "VerificationFailed(\"VRF output of primary slot claim is over the threshold\")",
+            ),
+            1,
+        );
+    }
+
+    fn create_aura_header(header: Header, slot_number: u64) -> Header {
+        let mut next_header = create_next_header(header);
+        next_header
+            .digest_mut()
+            .push(DigestItem::PreRuntime(AURA_ENGINE_ID, slot_number.encode()));
+        next_header
+    }
+
+    fn seal_aura_header<P: Pair>(mut header: Header, pair: &P) -> Header {
+        let signature = pair.sign(header.hash().as_ref());
+        header.digest_mut().push(DigestItem::Seal(
+            AURA_ENGINE_ID,
+            signature.as_ref().to_vec(),
+        ));
+        header
+    }
+
+    fn change_aura_authorities(mut header: Header, authorities: Vec<[u8; 32]>) -> Header {
+        header.digest_mut().push(DigestItem::Consensus(
+            AURA_ENGINE_ID,
+            AuraConsensusLog::AuthoritiesChange(authorities).encode(),
+        ));
+        header
+    }
+
+    #[test]
+    fn test_aura_seal_verification() {
+        write_test_flow(format!("Starting Aura seal verification test"));
+        let alice = sr25519::Pair::from_seed(&[1; 32]);
+        let bob = sr25519::Pair::from_seed(&[2; 32]);
+        let charlie = ed25519::Pair::from_seed(&[3; 32]);
+        let (encoded_data, initial_header) = assert_successful_db_init(None, 1);
+        let db = decode_db(&encoded_data);
+        assert!(initialize_babe_epochs(db.clone(), babe_epoch(0, 0, &[&alice]), None).is_ok());
+        assert!(initialize_aura_authority_set(
+            db.clone(),
+            AuraAuthoritySet::new(AuraCrypto::Sr25519, vec![alice.public().0, bob.public().0]),
+        )
+        .is_ok());
+        // Headers are sealed by either BABE or Aura
+        assert_eq!(babe_epochs(db.clone()).unwrap(), (None, None));
+        let encoded_data = db.encode();
+
+        write_test_flow(format!(
+            "\n\nHeaders without justification need to carry slot and seal"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            create_next_header(initial_header.clone()),
+            None,
+            String::from("VerificationFailed(\"Header has no Aura pre-runtime digest\")"),
+            1,
+        );
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            create_aura_header(initial_header.clone(), 2),
+            None,
+            String::from("VerificationFailed(\"Header has bad Aura seal\")"),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nHeader sealed by authority not assigned to the slot is rejected"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_aura_header(create_aura_header(initial_header.clone(), 3), &alice),
+            None,
+            String::from("VerificationFailed(\"Bad signature on Aura seal\")"),
+            1,
+        );
+
+        write_test_flow(format!(
+            "\n\nHeader sealed by slot author is ingested, and changes authority set"
+        ));
+        let first_header = seal_aura_header(
+            change_aura_authorities(
+                create_aura_header(initial_header.clone(), 3),
+                vec![alice.public().0],
+            ),
+            &bob,
+        );
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, first_header.clone(), None, 1);
+        assert_eq!(
+            aura_authority_set(decode_db(&encoded_data)).unwrap(),
+            Some(AuraAuthoritySet::new(
+                AuraCrypto::Sr25519,
+                vec![alice.public().0]
+            ))
+        );
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_aura_header(create_aura_header(first_header.clone(), 5), &bob),
+            None,
+            String::from("VerificationFailed(\"Bad signature on Aura seal\")"),
+            1,
+        );
+        let second_header = seal_aura_header(create_aura_header(first_header.clone(), 5), &alice);
+        let encoded_data =
+            assert_successful_header_ingestion(encoded_data, second_header.clone(), None, 1);
+
+        write_test_flow(format!(
+            "\n\nHeader claiming a slot not following slot of its parent is rejected"
+        ));
+        for slot_number in &[4, 5] {
+            assert_failed_header_ingestion(
+                encoded_data.clone(),
+                seal_aura_header(
+                    create_aura_header(second_header.clone(), *slot_number),
+                    &alice,
+                ),
+                None,
+                format!(
+                    "VerificationFailed(\"Slot {} of header does not follow slot 5 of its parent\")",
+                    slot_number
+                ),
+                1,
+            );
+        }
+
+        write_test_flow(format!(
+            "\n\nAuthority set change need to have at least one authority"
+        ));
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_aura_header(
+                change_aura_authorities(create_aura_header(second_header.clone(), 6), vec![]),
+                &alice,
+            ),
+            None,
+            String::from(
+                "VerificationFailed(\"Aura authority set change need to have at least one authority\")",
+            ),
+            1,
+        );
+
+        write_test_flow(format!("\n\nEd25519 seals are verified as well"));
+        let db = decode_db(&encoded_data);
+        assert!(initialize_aura_authority_set(
+            db.clone(),
+            AuraAuthoritySet::new(AuraCrypto::Ed25519, vec![charlie.public().0]),
+        )
+        .is_ok());
+        let encoded_data = db.encode();
+        assert_failed_header_ingestion(
+            encoded_data.clone(),
+            seal_aura_header(create_aura_header(second_header.clone(), 6), &alice),
+            None,
+            String::from("VerificationFailed(\"Bad signature on Aura seal\")"),
+            1,
+        );
+        assert_successful_header_ingestion(
+            encoded_data,
+            seal_aura_header(create_aura_header(second_header.clone(), 6), &charlie),
+            None,
+            1,
+        );
+    }
 }
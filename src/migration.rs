@@ -0,0 +1,318 @@
+use std::io;
+
+use kvdb::{DBTransaction, KeyValueDB};
+use parity_scale_codec::{Decode, Encode};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+
+use crate::common::types::blockchain_error::BlockchainError;
+use crate::common::types::blockchain_result::BlockchainResult;
+use crate::common::types::next_change_in_authority::NextChangeInAuthority;
+use crate::common::types::pruning_policy::PruningPolicy;
+use crate::common::utils::{NEXT_CHANGE_IN_AUTHORITY_KEY, PRUNING_POLICY_KEY};
+use crate::storage::{
+    StorageMeta, AUX_COLUMN, HEADER_COLUMN, LOOKUP_COLUMN, META_COLUMN, META_KEY, STATE_VERSION_KEY,
+};
+
+/// Version of the format in which light client state is persisted. It need to be
+/// bumped whenever encoding of any persisted type changes, along with adding an
+/// upgrade function from the previous version to `migrate_db`.
+pub(crate) const CURRENT_STATE_VERSION: u16 = 2;
+
+/// Databases written before state was versioned don't record their version.
+const UNVERSIONED_STATE_VERSION: u16 = 1;
+
+/// Types as they were encoded in version 1, before state was versioned. They are
+/// kept apart from the current ones, so that version 1 state can still be decoded.
+/// Light authority set is encoded the same way since, so it isn't upgraded.
+mod v1 {
+    use parity_scale_codec::{Decode, Encode};
+    use sp_finality_grandpa::ScheduledChange;
+
+    #[derive(Encode, Decode)]
+    pub struct StorageMeta<N, H> {
+        pub best_hash: H,
+        pub best_number: N,
+        pub finalized_hash: H,
+        pub finalized_number: N,
+        pub genesis_hash: H,
+        pub total_stored: u64,
+        pub oldest_stored_hash: H,
+    }
+
+    #[derive(Encode, Decode)]
+    pub struct NextChangeInAuthority<N, H> {
+        pub next_change_at: N,
+        pub block_enacting_this_change: (H, N),
+        pub change: ScheduledChange<N>,
+    }
+}
+
+fn db_err(err: io::Error) -> BlockchainError {
+    BlockchainError::Backend(format!("{}", err))
+}
+
+fn decode_value<T: Decode>(encoded_value: &[u8], name: &str) -> BlockchainResult<T> {
+    T::decode(&mut &encoded_value[..]).map_err(|e| {
+        BlockchainError::DataDecode(format!("Unable to decode {}. Error: {}", name, e.what()))
+    })
+}
+
+/// Gives version of the format in which state in given database is persisted.
+pub(crate) fn state_version<D: KeyValueDB>(db: &D) -> BlockchainResult<u16> {
+    match db.get(META_COLUMN, STATE_VERSION_KEY).map_err(db_err)? {
+        Some(encoded_version) => decode_value(&encoded_version, "state version"),
+        None => Ok(UNVERSIONED_STATE_VERSION),
+    }
+}
+
+pub(crate) fn tx_store_state_version(tx: &mut DBTransaction, version: u16) {
+    tx.put(META_COLUMN, STATE_VERSION_KEY, version.encode().as_slice());
+}
+
+/// Upgrades state persisted in given database to the current version, one
+/// version at a time, each in its own transaction. Gives the version state
+/// was at before, which is the current one if there was nothing to upgrade.
+pub(crate) fn migrate_db<Block, D>(db: &D) -> BlockchainResult<u16>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    let from_version = state_version(db)?;
+    if from_version > CURRENT_STATE_VERSION {
+        return Err(BlockchainError::Backend(format!(
+            "State version {} is newer than the latest supported version {}",
+            from_version, CURRENT_STATE_VERSION
+        )));
+    }
+
+    let mut version = from_version;
+    while version < CURRENT_STATE_VERSION {
+        let mut tx = db.transaction();
+        match version {
+            1 => upgrade_from_v1::<Block, _>(db, &mut tx)?,
+            _ => {
+                return Err(BlockchainError::Backend(format!(
+                    "No upgrade from state version {}",
+                    version
+                )))
+            }
+        }
+        version += 1;
+        tx_store_state_version(&mut tx, version);
+        db.write(tx).map_err(db_err)?;
+    }
+
+    Ok(from_version)
+}
+
+/// Upgrades state written before it was versioned. Headers are encoded as
+/// defined by the chain, so they are kept as they are.
+fn upgrade_from_v1<Block, D>(db: &D, tx: &mut DBTransaction) -> BlockchainResult<()>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    if let Some(encoded_meta) = db.get(META_COLUMN, META_KEY).map_err(db_err)? {
+        let meta: v1::StorageMeta<NumberFor<Block>, Block::Hash> =
+            decode_value(&encoded_meta, "version 1 storage meta")?;
+        tx_store_lookups_from_v1::<Block, _>(db, tx, &meta)?;
+        // Headers couldn't skip ahead of the best one, so there are no gaps
+        let meta = StorageMeta {
+            best_hash: meta.best_hash,
+            best_number: meta.best_number,
+            finalized_hash: meta.finalized_hash,
+            finalized_number: meta.finalized_number,
+            genesis_hash: meta.genesis_hash,
+            total_stored: meta.total_stored,
+            oldest_stored_hash: meta.oldest_stored_hash,
+            gaps: vec![],
+        };
+        tx.put(META_COLUMN, META_KEY, meta.encode().as_slice());
+    }
+
+    // Forced changes weren't tracked, so the pending change is a standard one
+    if let Some(encoded_change) = db
+        .get(AUX_COLUMN, NEXT_CHANGE_IN_AUTHORITY_KEY)
+        .map_err(db_err)?
+    {
+        let change: v1::NextChangeInAuthority<NumberFor<Block>, Block::Hash> =
+            decode_value(&encoded_change, "version 1 next change in authority")?;
+        let change = NextChangeInAuthority::<Block>::new(
+            change.next_change_at,
+            change.block_enacting_this_change,
+            change.change,
+        );
+        tx.put(
+            AUX_COLUMN,
+            NEXT_CHANGE_IN_AUTHORITY_KEY,
+            change.encode().as_slice(),
+        );
+    }
+
+    // Pruning was count based, so no header is retained as checkpoint
+    tx.put(
+        AUX_COLUMN,
+        PRUNING_POLICY_KEY,
+        PruningPolicy::default().encode().as_slice(),
+    );
+
+    Ok(())
+}
+
+/// Version 1 didn't write lookup entries, so headers are found by
+/// walking back from the best header through parent hashes.
+fn tx_store_lookups_from_v1<Block, D>(
+    db: &D,
+    tx: &mut DBTransaction,
+    meta: &v1::StorageMeta<NumberFor<Block>, Block::Hash>,
+) -> BlockchainResult<()>
+where
+    Block: BlockT,
+    D: KeyValueDB,
+{
+    let mut current_hash = meta.best_hash;
+    for _ in 0..meta.total_stored {
+        let encoded_header = db
+            .get(HEADER_COLUMN, current_hash.encode().as_slice())
+            .map_err(db_err)?
+            .ok_or_else(|| {
+                BlockchainError::Backend(format!(
+                    "Version 1 state is missing stored header {:?}",
+                    current_hash
+                ))
+            })?;
+        let header: Block::Header = decode_value(&encoded_header, "version 1 header")?;
+        tx.put(
+            LOOKUP_COLUMN,
+            header.number().encode().as_slice(),
+            current_hash.encode().as_slice(),
+        );
+        current_hash = *header.parent_hash();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use kvdb::KeyValueDB;
+    use parity_scale_codec::{Decode, Encode};
+    use sp_core::H256;
+    use sp_runtime::traits::Header as HeaderT;
+
+    use crate::common::types::pruning_policy::PruningPolicy;
+    use crate::common::utils::{NEXT_CHANGE_IN_AUTHORITY_KEY, NUM_COLUMNS};
+    use crate::db::{create, Data};
+    use crate::light_state::{
+        check_integrity, current_status, header_by_number, pruning_policy, stored_headers_info,
+    };
+    use crate::migration::{
+        migrate_db, state_version, tx_store_state_version, CURRENT_STATE_VERSION,
+    };
+    use crate::storage::AUX_COLUMN;
+    use crate::types::Block;
+
+    /// Light client data, as kept in contract state by clients instantiated before
+    /// state was versioned. Client was initialized with header 55439 of Kusama, and
+    /// ingested a header following it which schedules authority set change.
+    const V1_LIGHT_CLIENT_DATA: &str = include_str!("../fixtures/v1/light_client_data.hex");
+
+    fn v1_data() -> Data {
+        let encoded_data = hex::decode(V1_LIGHT_CLIENT_DATA.trim()).unwrap();
+        Data::decode(&mut encoded_data.as_slice()).unwrap()
+    }
+
+    fn hash(hex_hash: &str) -> H256 {
+        H256::from_slice(&hex::decode(hex_hash).unwrap())
+    }
+
+    #[test]
+    fn test_migrate_db_upgrades_v1_fixture() {
+        let db = v1_data().db;
+        assert_eq!(state_version(&db).unwrap(), 1);
+
+        assert_eq!(migrate_db::<Block, _>(&db).unwrap(), 1);
+        assert_eq!(state_version(&db).unwrap(), CURRENT_STATE_VERSION);
+
+        let init_header_hash =
+            hash("f157283bcfe5ace5f3258bdb595ee8c6761394a56c8e73b6aaf734e6fb1e7c92");
+        let change_header_hash =
+            hash("9a2ba1787bfa18da6b5b51d82af3b5e3d21d6e8e1fbf32da5d4cbe9e75812220");
+        let status = current_status::<Block, _>(db.clone()).unwrap();
+        let best_header = status.possible_best_header.unwrap();
+        assert_eq!(best_header.hash(), change_header_hash);
+        assert_eq!(*best_header.number(), 55440);
+        assert!(status.possible_last_finalized_header.is_none());
+        assert!(status.gaps.is_empty());
+
+        let authority_set = status.possible_light_authority_set.unwrap();
+        assert_eq!(authority_set.set_id(), 1);
+        assert_eq!(authority_set.authorities().len(), 1);
+
+        let next_change = status.possible_next_change_in_authority.unwrap();
+        assert_eq!(next_change.next_change_at, 55450);
+        assert_eq!(
+            next_change.block_enacting_this_change,
+            (change_header_hash, 55440)
+        );
+        assert_eq!(next_change.change.delay, 10);
+        assert_eq!(
+            next_change.change.next_authorities,
+            authority_set.authorities()
+        );
+        assert!(!next_change.is_forced());
+        assert!(status.possible_next_forced_change_in_authority.is_none());
+
+        assert_eq!(
+            pruning_policy(db.clone()).unwrap(),
+            PruningPolicy::default()
+        );
+
+        // Headers are found by number through lookup entries written by upgrade
+        for (number, expected_hash) in &[(55439, init_header_hash), (55440, change_header_hash)] {
+            let header = header_by_number::<Block, _>(db.clone(), *number)
+                .unwrap()
+                .unwrap();
+            assert_eq!(header.hash(), *expected_hash);
+        }
+
+        let info = stored_headers_info::<Block, _>(db.clone()).unwrap();
+        assert_eq!(info.total_stored, 2);
+        assert_eq!(info.oldest_stored_hash, init_header_hash);
+        assert!(info.checkpoints.is_empty());
+        assert!(check_integrity::<Block, _>(db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_db_keeps_current_state() {
+        let db = v1_data().db;
+        assert!(migrate_db::<Block, _>(&db).is_ok());
+        let encoded_db = db.encode();
+
+        // There is nothing to upgrade
+        assert_eq!(migrate_db::<Block, _>(&db).unwrap(), CURRENT_STATE_VERSION);
+        assert_eq!(db.encode(), encoded_db);
+    }
+
+    #[test]
+    fn test_migrate_db_refuses_newer_state() {
+        let db = create(NUM_COLUMNS);
+        let mut tx = db.transaction();
+        tx_store_state_version(&mut tx, CURRENT_STATE_VERSION + 1);
+        assert!(db.write(tx).is_ok());
+
+        assert!(migrate_db::<Block, _>(&db).is_err());
+    }
+
+    #[test]
+    fn test_migrate_db_refuses_malformed_v1_state() {
+        let db = v1_data().db;
+        let mut tx = db.transaction();
+        tx.put(AUX_COLUMN, NEXT_CHANGE_IN_AUTHORITY_KEY, &[1, 2, 3]);
+        assert!(db.write(tx).is_ok());
+
+        assert!(migrate_db::<Block, _>(&db).is_err());
+        // Nothing is written if upgrade fails
+        assert_eq!(state_version(&db).unwrap(), 1);
+    }
+}
@@ -0,0 +1,298 @@
+use std::io;
+
+use cosmwasm_std::{ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use kvdb::{DBOp, DBTransaction, DBValue, KeyValueDB};
+use parity_scale_codec::alloc::collections::BTreeMap;
+use parity_scale_codec::alloc::sync::Arc;
+use parity_util_mem::{MallocSizeOf, MallocSizeOfOps};
+use parking_lot::RwLock;
+
+use crate::db::DB;
+use crate::storage::{AUX_COLUMN, HEADER_COLUMN, LOOKUP_COLUMN, META_COLUMN};
+
+// Columns used by light client storage, along with prefix
+// under which each one of them is kept in contract storage.
+const COLUMNS: [(u32, &[u8]); 4] = [
+    (META_COLUMN, b"db_meta"),
+    (HEADER_COLUMN, b"db_header"),
+    (AUX_COLUMN, b"db_aux"),
+    (LOOKUP_COLUMN, b"db_lookup"),
+];
+
+// Value is `None` if key is deleted
+type Changes = BTreeMap<(&'static [u8], Vec<u8>), Option<DBValue>>;
+
+fn column_namespace(col: u32) -> Option<&'static [u8]> {
+    COLUMNS
+        .iter()
+        .find(|(column, _)| *column == col)
+        .map(|(_, namespace)| *namespace)
+}
+
+fn no_such_column(col: u32) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("No such column family: {:?}", col),
+    )
+}
+
+/// Database which maps columns used by light client storage onto prefixed
+/// keys of contract storage. Keys are read from contract storage only when
+/// they are requested, while writes are buffered in memory until they are
+/// committed, so that only touched keys are written, and nothing is written
+/// if ingestion fails halfway.
+pub struct PrefixedDB<'a, S> {
+    storage: &'a S,
+    changes: Arc<RwLock<Changes>>,
+    // Column which buffered write referred to, despite not being supported.
+    // Once it is recorded, reads, flushes and commit fail.
+    unsupported_column: Arc<RwLock<Option<u32>>>,
+}
+
+impl<'a, S> PrefixedDB<'a, S>
+where
+    S: ReadonlyStorage,
+{
+    pub fn new(storage: &'a S) -> Self {
+        Self {
+            storage,
+            changes: Arc::new(RwLock::new(BTreeMap::new())),
+            unsupported_column: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Copies content of supported columns of in-memory database into
+    /// buffered changes, so that it is written to contract storage on commit.
+    pub fn import(&self, db: &DB) {
+        let mut changes = self.changes.write();
+        for (col, namespace) in COLUMNS.iter() {
+            for (key, value) in db.iter(*col) {
+                changes.insert((*namespace, key.into_vec()), Some(value.into_vec()));
+            }
+        }
+    }
+
+    /// Gives changes buffered so far, releasing contract storage,
+    /// so that changes can be committed to it.
+    pub fn into_changes(self) -> PrefixedDBChanges {
+        PrefixedDBChanges {
+            changes: self.changes.read().clone(),
+            unsupported_column: *self.unsupported_column.read(),
+        }
+    }
+
+    fn ensure_no_unsupported_write(&self) -> io::Result<()> {
+        match *self.unsupported_column.read() {
+            Some(col) => Err(no_such_column(col)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, S> Clone for PrefixedDB<'a, S> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: self.storage,
+            changes: self.changes.clone(),
+            unsupported_column: self.unsupported_column.clone(),
+        }
+    }
+}
+
+impl<'a, S> MallocSizeOf for PrefixedDB<'a, S> {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        // Contract storage isn't held in memory, only buffered changes are
+        self.changes.read().size_of(ops)
+    }
+}
+
+impl<'a, S> KeyValueDB for PrefixedDB<'a, S>
+where
+    S: ReadonlyStorage + Sync,
+{
+    fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>> {
+        self.ensure_no_unsupported_write()?;
+        let namespace = column_namespace(col).ok_or_else(|| no_such_column(col))?;
+        if let Some(possible_value) = self.changes.read().get(&(namespace, key.to_vec())) {
+            return Ok(possible_value.clone());
+        }
+        ReadonlyPrefixedStorage::new(namespace, self.storage)
+            .get(key)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))
+    }
+
+    // Contract storage can't be iterated over without `iterator` feature,
+    // and light client storage only looks up exact keys, so nothing is found.
+    fn get_by_prefix(&self, _col: u32, _prefix: &[u8]) -> Option<Box<[u8]>> {
+        None
+    }
+
+    // Write can't fail, so write to unsupported column is recorded
+    // and reported by `flush`, which `write` calls right after.
+    fn write_buffered(&self, transaction: DBTransaction) {
+        let mut changes = self.changes.write();
+        for op in transaction.ops {
+            let (col, key, possible_value) = match op {
+                DBOp::Insert { col, key, value } => (col, key, Some(value)),
+                DBOp::Delete { col, key } => (col, key, None),
+            };
+            match column_namespace(col) {
+                Some(namespace) => {
+                    changes.insert((namespace, key.into_vec()), possible_value);
+                }
+                None => {
+                    self.unsupported_column.write().get_or_insert(col);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.ensure_no_unsupported_write()
+    }
+
+    fn iter<'b>(&'b self, _col: u32) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'b> {
+        Box::new(std::iter::empty())
+    }
+
+    fn iter_from_prefix<'b>(
+        &'b self,
+        _col: u32,
+        _prefix: &'b [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'b> {
+        Box::new(std::iter::empty())
+    }
+
+    fn restore(&self, _new_db: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Attempted to restore database backed by contract storage",
+        ))
+    }
+}
+
+/// Changes buffered by `PrefixedDB`, which are yet to be written to contract storage.
+pub struct PrefixedDBChanges {
+    changes: Changes,
+    unsupported_column: Option<u32>,
+}
+
+impl PrefixedDBChanges {
+    /// Writes changes to contract storage, unless any
+    /// of them was made to an unsupported column.
+    pub fn commit<S: Storage>(self, storage: &mut S) -> StdResult<()> {
+        if let Some(col) = self.unsupported_column {
+            return Err(StdError::GenericErr {
+                msg: no_such_column(col).to_string(),
+                backtrace: None,
+            });
+        }
+        for ((namespace, key), possible_value) in self.changes {
+            let mut prefixed_storage = PrefixedStorage::new(namespace, &mut *storage);
+            match possible_value {
+                Some(value) => prefixed_storage.set(&key, &value)?,
+                None => prefixed_storage.remove(&key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::ReadonlyStorage;
+    use kvdb::KeyValueDB;
+
+    use crate::db::create;
+    use crate::prefixed_db::PrefixedDB;
+    use crate::storage::{AUX_COLUMN, HEADER_COLUMN, META_COLUMN};
+
+    #[test]
+    fn prefixed_db_writes_only_on_commit() {
+        let mut storage = MockStorage::new();
+
+        let db = PrefixedDB::new(&storage);
+        let mut transaction = db.transaction();
+        transaction.put(META_COLUMN, b"key1", b"horse");
+        transaction.put(HEADER_COLUMN, b"key1", b"pigeon");
+        assert!(db.write(transaction).is_ok());
+        // Buffered changes are visible through database
+        assert_eq!(db.get(META_COLUMN, b"key1").unwrap().unwrap(), b"horse");
+        assert_eq!(db.get(HEADER_COLUMN, b"key1").unwrap().unwrap(), b"pigeon");
+        assert!(db.get(AUX_COLUMN, b"key1").unwrap().is_none());
+
+        let changes = db.into_changes();
+        assert!(storage.get(b"key1").unwrap().is_none());
+        assert!(changes.commit(&mut storage).is_ok());
+
+        let db = PrefixedDB::new(&storage);
+        assert_eq!(db.get(META_COLUMN, b"key1").unwrap().unwrap(), b"horse");
+        assert_eq!(db.get(HEADER_COLUMN, b"key1").unwrap().unwrap(), b"pigeon");
+        // Columns don't overlap in contract storage
+        assert!(db.get(AUX_COLUMN, b"key1").unwrap().is_none());
+
+        let mut transaction = db.transaction();
+        transaction.delete(META_COLUMN, b"key1");
+        assert!(db.write(transaction).is_ok());
+        assert!(db.get(META_COLUMN, b"key1").unwrap().is_none());
+        let changes = db.into_changes();
+        assert!(changes.commit(&mut storage).is_ok());
+
+        let db = PrefixedDB::new(&storage);
+        assert!(db.get(META_COLUMN, b"key1").unwrap().is_none());
+        assert_eq!(db.get(HEADER_COLUMN, b"key1").unwrap().unwrap(), b"pigeon");
+    }
+
+    #[test]
+    fn prefixed_db_get_fails_with_non_existing_column() {
+        let storage = MockStorage::new();
+        let db = PrefixedDB::new(&storage);
+        assert!(db.get(10, b"key1").is_err());
+    }
+
+    #[test]
+    fn prefixed_db_refuses_write_to_non_existing_column() {
+        let mut storage = MockStorage::new();
+        let db = PrefixedDB::new(&storage);
+        let mut transaction = db.transaction();
+        transaction.put(META_COLUMN, b"key1", b"horse");
+        transaction.put(10, b"key1", b"pigeon");
+        assert_eq!(
+            db.write(transaction).err().unwrap().to_string(),
+            "No such column family: 10"
+        );
+
+        // Database stays unusable, so that partial write isn't committed
+        assert!(db.get(META_COLUMN, b"key1").is_err());
+        assert!(db.get_by_prefix(META_COLUMN, b"key").is_none());
+        assert_eq!(db.iter(META_COLUMN).count(), 0);
+        assert!(db.into_changes().commit(&mut storage).is_err());
+        assert!(storage.get(b"key1").unwrap().is_none());
+        assert!(PrefixedDB::new(&storage)
+            .get(META_COLUMN, b"key1")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn prefixed_db_imports_in_memory_db() {
+        let mut storage = MockStorage::new();
+
+        let in_memory_db = create(4);
+        let mut transaction = in_memory_db.transaction();
+        transaction.put(META_COLUMN, b"key1", b"horse");
+        transaction.put(AUX_COLUMN, b"key2", b"cat");
+        assert!(in_memory_db.write(transaction).is_ok());
+
+        let db = PrefixedDB::new(&storage);
+        db.import(&in_memory_db);
+        let changes = db.into_changes();
+        assert!(changes.commit(&mut storage).is_ok());
+
+        let db = PrefixedDB::new(&storage);
+        assert_eq!(db.get(META_COLUMN, b"key1").unwrap().unwrap(), b"horse");
+        assert_eq!(db.get(AUX_COLUMN, b"key2").unwrap().unwrap(), b"cat");
+    }
+}
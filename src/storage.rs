@@ -7,24 +7,39 @@ use crate::common::types::blockchain_error::BlockchainError;
 use crate::common::types::blockchain_info::BlockchainInfo;
 use crate::common::types::blockchain_result::BlockchainResult;
 use crate::common::types::cached_header_metadata::CachedHeaderMetadata;
+use crate::common::types::integrity_violation::IntegrityViolation;
+use crate::common::types::light_authority_set::LightAuthoritySet;
 use crate::common::types::new_block_state::NewBlockState;
-use crate::db::Data;
+use crate::common::types::pruning_policy::PruningPolicy;
+use crate::common::types::storage_info::StorageInfo;
+use crate::common::utils::{
+    fetch_authority_change, AURA_AUTHORITY_SET_KEY, BABE_EPOCH_KEY, BABE_NEXT_EPOCH_KEY,
+    BEEFY_MMR_ROOT_KEY, BEEFY_NEXT_AUTHORITY_SET_KEY, BEEFY_VALIDATOR_SET_KEY, CHECKPOINT_PREFIX,
+    LATEST_CHECKPOINT_KEY, LIGHT_AUTHORITY_SET_KEY, NEXT_CHANGE_IN_AUTHORITY_KEY,
+    NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY, PRUNING_POLICY_KEY,
+};
+use crate::migration::{state_version, tx_store_state_version, CURRENT_STATE_VERSION};
+use crate::verifier::{find_forced_change, find_scheduled_change};
 use kvdb::{DBTransaction, KeyValueDB};
 use parity_scale_codec::{Decode, Encode};
 use sp_runtime::generic::BlockId;
-use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, One, Zero};
+use sp_runtime::traits::{
+    Block as BlockT, Header as HeaderT, NumberFor, One, Saturating, UniqueSaturatedInto, Zero,
+};
 use std::io;
 
-const META_COLUMN: u32 = 0;
-const HEADER_COLUMN: u32 = 1;
-const AUX_COLUMN: u32 = 2;
-const LOOKUP_COLUMN: u32 = 3;
+pub(crate) const META_COLUMN: u32 = 0;
+pub(crate) const HEADER_COLUMN: u32 = 1;
+pub(crate) const AUX_COLUMN: u32 = 2;
+pub(crate) const LOOKUP_COLUMN: u32 = 3;
 
-const META_KEY: &[u8] = b"meta";
+pub(crate) const META_KEY: &[u8] = b"meta";
+/// Version of the format in which state is persisted is kept under this key in meta column.
+pub(crate) const STATE_VERSION_KEY: &[u8] = b"state_version";
 
 /// Database metadata.
 #[derive(Debug, Encode, Decode)]
-struct StorageMeta<N, H>
+pub(crate) struct StorageMeta<N, H>
 where
     N: Encode + Decode,
     H: Encode + Decode,
@@ -43,6 +58,22 @@ where
     pub total_stored: u64,
     /// Oldest stored header's corresponding block hash
     pub oldest_stored_hash: H,
+    /// Inclusive ranges of block numbers skipped by finalized headers
+    /// imported ahead of the best header.
+    pub gaps: Vec<(N, N)>,
+}
+
+/// Header retained as checkpoint, kept under its own key in aux storage.
+#[derive(Debug, Encode, Decode)]
+struct Checkpoint<N, H>
+where
+    N: Encode + Decode,
+    H: Encode + Decode,
+{
+    /// Hash of the retained header.
+    hash: H,
+    /// Number of the checkpoint retained before this one.
+    previous_number: Option<N>,
 }
 
 fn db_err(err: io::Error) -> BlockchainError {
@@ -53,20 +84,23 @@ fn codec_error(err: parity_scale_codec::Error) -> BlockchainError {
     BlockchainError::DataDecode(err.to_string())
 }
 
-pub struct Storage {
-    data: Data,
+pub struct Storage<D> {
+    db: D,
     max_headers_allowed_to_store: u64,
 }
 
-impl Storage {
-    pub fn new(data: Data, max_headers_allowed_to_store: u64) -> Result<Self, BlockchainError> {
+impl<D> Storage<D>
+where
+    D: KeyValueDB,
+{
+    pub fn new(db: D, max_headers_allowed_to_store: u64) -> Result<Self, BlockchainError> {
         if max_headers_allowed_to_store < 2 {
             Err(BlockchainError::Backend(
                 "Maximum amount of blocks allowed to store need to be at least 2".into(),
             ))
         } else {
             Ok(Self {
-                data,
+                db,
                 max_headers_allowed_to_store,
             })
         }
@@ -77,7 +111,7 @@ impl Storage {
         N: Encode + Decode,
         H: Encode + Decode,
     {
-        let possible_encoded_meta = self.data.db.get(META_COLUMN, META_KEY).map_err(db_err)?;
+        let possible_encoded_meta = self.db.get(META_COLUMN, META_KEY).map_err(db_err)?;
         if possible_encoded_meta.is_none() {
             Ok(None)
         } else {
@@ -93,9 +127,9 @@ impl Storage {
         N: Encode + Decode,
         H: Encode + Decode,
     {
-        let mut tx = self.data.db.transaction();
+        let mut tx = self.db.transaction();
         Self::tx_store_meta(&mut tx, &meta);
-        self.data.db.write(tx).map_err(db_err)
+        self.db.write(tx).map_err(db_err)
     }
 
     fn tx_store_meta<N, H>(tx: &mut DBTransaction, meta: &StorageMeta<N, H>)
@@ -122,6 +156,629 @@ impl Storage {
         tx.delete(HEADER_COLUMN, id.as_slice());
     }
 
+    fn tx_store_lookup<Block>(tx: &mut DBTransaction, header: &Block::Header)
+    where
+        Block: BlockT,
+    {
+        tx.put(
+            LOOKUP_COLUMN,
+            header.number().encode().as_slice(),
+            header.hash().encode().as_slice(),
+        );
+    }
+
+    fn tx_delete_lookup<Block>(tx: &mut DBTransaction, number: &NumberFor<Block>)
+    where
+        Block: BlockT,
+    {
+        tx.delete(LOOKUP_COLUMN, number.encode().as_slice());
+    }
+
+    /// Deletes headers which are not finalized yet, starting from the best header.
+    /// Returns number of the oldest deleted header, or number following the best
+    /// header if nothing is deleted.
+    fn tx_discard_non_finalized<Block>(
+        &self,
+        tx: &mut DBTransaction,
+        meta: &mut StorageMeta<NumberFor<Block>, Block::Hash>,
+    ) -> BlockchainResult<NumberFor<Block>>
+    where
+        Block: BlockT,
+    {
+        // Initial header is trusted, so it is treated as finalized
+        let last_finalized_hash = if meta.finalized_hash == Default::default() {
+            meta.genesis_hash
+        } else {
+            meta.finalized_hash
+        };
+
+        let mut possible_first_discarded_number = None;
+        let mut current_hash = meta.best_hash;
+        while current_hash != last_finalized_hash {
+            let possible_header = self.header(BlockId::<Block>::Hash(current_hash))?;
+            if possible_header.is_none() {
+                // Rest of the headers are already pruned
+                break;
+            }
+            let header = possible_header.unwrap();
+            Self::tx_delete_header::<Block>(tx, &current_hash);
+            Self::tx_delete_lookup::<Block>(tx, header.number());
+            meta.total_stored -= 1;
+            possible_first_discarded_number = Some(*header.number());
+            current_hash = *header.parent_hash();
+        }
+
+        meta.best_hash = current_hash;
+        match possible_first_discarded_number {
+            Some(first_discarded_number) => {
+                meta.best_number = first_discarded_number - One::one();
+                Ok(first_discarded_number)
+            }
+            // Best header at the highest possible number can't be followed anyway
+            None => Ok(meta.best_number.saturating_add(One::one())),
+        }
+    }
+
+    /// Gives hash of the stored header preceding given one, which is its parent
+    /// unless given header follows a gap.
+    fn previous_stored_hash<Block>(
+        &self,
+        header: &Block::Header,
+        gaps: &[(NumberFor<Block>, NumberFor<Block>)],
+    ) -> BlockchainResult<Block::Hash>
+    where
+        Block: BlockT,
+    {
+        for (start, end) in gaps {
+            if *end + One::one() == *header.number() {
+                if let Some(hash) = self.header_hash::<Block>(*start - One::one())? {
+                    return Ok(hash);
+                }
+            }
+        }
+        Ok(*header.parent_hash())
+    }
+
+    /// Gives number of the stored header following the one with given
+    /// number, which is the next number unless a gap follows it.
+    pub(crate) fn next_stored_number<Block>(
+        number: NumberFor<Block>,
+        gaps: &[(NumberFor<Block>, NumberFor<Block>)],
+    ) -> NumberFor<Block>
+    where
+        Block: BlockT,
+    {
+        let next_number = number + One::one();
+        gaps.iter()
+            .find(|(start, _)| *start == next_number)
+            .map_or(next_number, |(_, end)| *end + One::one())
+    }
+
+    /// Gives hashes of headers which can't be pruned, that is the last finalized
+    /// header, unless header being imported replaces it, and headers signalling
+    /// pending authority set changes.
+    fn pinned_hashes<Block>(
+        &self,
+        meta: &StorageMeta<NumberFor<Block>, Block::Hash>,
+        finalizing: bool,
+    ) -> BlockchainResult<Vec<Block::Hash>>
+    where
+        Block: BlockT,
+    {
+        let mut pinned_hashes = vec![];
+        if !finalizing {
+            // Initial header is trusted, so it is treated as finalized
+            pinned_hashes.push(if meta.finalized_hash == Default::default() {
+                meta.genesis_hash
+            } else {
+                meta.finalized_hash
+            });
+        }
+        for key in &[
+            NEXT_CHANGE_IN_AUTHORITY_KEY,
+            NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY,
+        ] {
+            if let Some(change) = fetch_authority_change::<Self, Block>(self, *key)? {
+                pinned_hashes.push(change.block_enacting_this_change.0);
+            }
+        }
+        Ok(pinned_hashes)
+    }
+
+    /// Tells if header is retained as checkpoint once it is pruned.
+    fn is_checkpoint<Block>(header: &Block::Header, pruning_policy: &PruningPolicy) -> bool
+    where
+        Block: BlockT,
+    {
+        let number = UniqueSaturatedInto::<u64>::unique_saturated_into(*header.number());
+        (pruning_policy.checkpoint_interval > 0 && number % pruning_policy.checkpoint_interval == 0)
+            || (pruning_policy.checkpoint_authority_set_changes
+                && (find_scheduled_change::<Block>(header).is_some()
+                    || find_forced_change::<Block>(header).is_some()))
+    }
+
+    /// Gives policy deciding which headers are retained as checkpoints.
+    pub fn pruning_policy(&self) -> BlockchainResult<PruningPolicy> {
+        let possible_encoded_policy = self.get_aux(PRUNING_POLICY_KEY)?;
+        if possible_encoded_policy.is_none() {
+            Ok(PruningPolicy::default())
+        } else {
+            let encoded_policy = possible_encoded_policy.unwrap();
+            PruningPolicy::decode(&mut encoded_policy.as_slice()).map_err(codec_error)
+        }
+    }
+
+    /// Sets policy deciding which headers are retained as checkpoints.
+    pub fn set_pruning_policy(&self, pruning_policy: &PruningPolicy) -> BlockchainResult<()> {
+        self.insert_aux(
+            &[(PRUNING_POLICY_KEY, pruning_policy.encode().as_slice())],
+            &[],
+        )
+    }
+
+    /// Gives number and hash of headers retained as checkpoints after being pruned,
+    /// the oldest one first.
+    pub fn checkpoints<Block>(&self) -> BlockchainResult<Vec<(NumberFor<Block>, Block::Hash)>>
+    where
+        Block: BlockT,
+    {
+        self.fetch_checkpoints::<Block>().map_err(|(_, e)| e)
+    }
+
+    /// Walks checkpoints from the latest one back. Gives key of the
+    /// entry which can't be read, along with the error, if walk fails.
+    fn fetch_checkpoints<Block>(
+        &self,
+    ) -> Result<Vec<(NumberFor<Block>, Block::Hash)>, (Vec<u8>, BlockchainError)>
+    where
+        Block: BlockT,
+    {
+        let mut checkpoints = vec![];
+        let mut possible_number = self
+            .latest_checkpoint::<Block>()
+            .map_err(|e| (LATEST_CHECKPOINT_KEY.to_vec(), e))?;
+        while let Some(number) = possible_number {
+            let key = Self::checkpoint_key::<Block>(&number);
+            let checkpoint = match self.get_aux(&key) {
+                Ok(Some(encoded_checkpoint)) => {
+                    Checkpoint::<NumberFor<Block>, Block::Hash>::decode(
+                        &mut encoded_checkpoint.as_slice(),
+                    )
+                    .map_err(|e| (key.clone(), codec_error(e)))?
+                }
+                Ok(None) => {
+                    return Err((
+                        key,
+                        BlockchainError::Backend(format!(
+                            "FATAL: Storage inconsistency. Unable to retrieve checkpoint at {}",
+                            number
+                        )),
+                    ))
+                }
+                Err(e) => return Err((key, e)),
+            };
+            // Checkpoints are retained in ascending order, so link can't loop
+            if checkpoint
+                .previous_number
+                .map_or(false, |previous_number| previous_number >= number)
+            {
+                return Err((
+                    key,
+                    BlockchainError::Backend(format!(
+                        "FATAL: Storage inconsistency. Checkpoint at {} links to a later one",
+                        number
+                    )),
+                ));
+            }
+            checkpoints.push((number, checkpoint.hash));
+            possible_number = checkpoint.previous_number;
+        }
+        checkpoints.reverse();
+        Ok(checkpoints)
+    }
+
+    /// Gives number of the latest header retained as checkpoint.
+    fn latest_checkpoint<Block>(&self) -> BlockchainResult<Option<NumberFor<Block>>>
+    where
+        Block: BlockT,
+    {
+        let possible_encoded_number = self.get_aux(LATEST_CHECKPOINT_KEY)?;
+        if possible_encoded_number.is_none() {
+            Ok(None)
+        } else {
+            let encoded_number = possible_encoded_number.unwrap();
+            Ok(Some(
+                NumberFor::<Block>::decode(&mut encoded_number.as_slice()).map_err(codec_error)?,
+            ))
+        }
+    }
+
+    fn checkpoint_key<Block>(number: &NumberFor<Block>) -> Vec<u8>
+    where
+        Block: BlockT,
+    {
+        let mut key = CHECKPOINT_PREFIX.to_vec();
+        key.extend(number.encode());
+        key
+    }
+
+    fn tx_store_checkpoint<Block>(
+        tx: &mut DBTransaction,
+        number: &NumberFor<Block>,
+        hash: Block::Hash,
+        previous_number: Option<NumberFor<Block>>,
+    ) where
+        Block: BlockT,
+    {
+        let checkpoint = Checkpoint {
+            hash,
+            previous_number,
+        };
+        tx.put(
+            AUX_COLUMN,
+            &Self::checkpoint_key::<Block>(number),
+            checkpoint.encode().as_slice(),
+        );
+        tx.put(
+            AUX_COLUMN,
+            LATEST_CHECKPOINT_KEY,
+            number.encode().as_slice(),
+        );
+    }
+
+    /// Gives number of the stored header preceding the one with given
+    /// number, which is the previous number unless a gap precedes it.
+    fn previous_stored_number<Block>(
+        number: NumberFor<Block>,
+        gaps: &[(NumberFor<Block>, NumberFor<Block>)],
+    ) -> NumberFor<Block>
+    where
+        Block: BlockT,
+    {
+        gaps.iter()
+            .find(|(_, end)| *end + One::one() == number)
+            .map_or(number, |(start, _)| *start)
+            - One::one()
+    }
+
+    /// Tells if header with given hash is stored, and reachable by given number.
+    fn is_stored_at<Block>(&self, number: NumberFor<Block>, hash: Block::Hash) -> bool
+    where
+        Block: BlockT,
+    {
+        let possible_header: Option<Block::Header> =
+            self.header(BlockId::<Block>::Hash(hash)).ok().flatten();
+        possible_header.map_or(false, |header| *header.number() == number)
+            && self.header_hash::<Block>(number).ok().flatten() == Some(hash)
+    }
+
+    /// Walks stored headers from the best one back to the oldest one, checking them
+    /// against metadata, lookup column and aux storage, so that inconsistencies are
+    /// detected before import or finalization runs into them. Gives every
+    /// inconsistency found, and fails only if underlying database fails.
+    pub fn check_integrity<Block>(
+        &self,
+    ) -> BlockchainResult<Vec<IntegrityViolation<NumberFor<Block>, Block::Hash>>>
+    where
+        Block: BlockT,
+    {
+        let mut violations = vec![];
+        let meta: StorageMeta<NumberFor<Block>, Block::Hash> = match self.fetch_meta() {
+            Ok(Some(meta)) => meta,
+            Ok(None) => {
+                violations.push(IntegrityViolation::MissingMeta);
+                return Ok(violations);
+            }
+            Err(e) => {
+                violations.push(IntegrityViolation::InvalidMeta(e.to_string()));
+                return Ok(violations);
+            }
+        };
+
+        match state_version(&self.db) {
+            Ok(version) if version == CURRENT_STATE_VERSION => {}
+            Ok(version) => violations.push(IntegrityViolation::InvalidMeta(format!(
+                "State is at version {}, while version {} is expected",
+                version, CURRENT_STATE_VERSION
+            ))),
+            Err(e) => violations.push(IntegrityViolation::InvalidMeta(e.to_string())),
+        }
+
+        let mut found = 0;
+        let mut current_hash = meta.best_hash;
+        let mut possible_expected_number = None;
+        let mut finalized_header_found = false;
+        while found < meta.total_stored {
+            let possible_header: Option<Block::Header> = self
+                .header(BlockId::<Block>::Hash(current_hash))
+                .ok()
+                .flatten();
+            if possible_header.is_none() {
+                violations.push(IntegrityViolation::MissingHeader { hash: current_hash });
+                break;
+            }
+            let header = possible_header.unwrap();
+            let number = *header.number();
+            found += 1;
+
+            if header.hash() != current_hash {
+                violations.push(IntegrityViolation::HeaderHashMismatch { hash: current_hash });
+            }
+            if self.header_hash::<Block>(number)? != Some(current_hash) {
+                violations.push(IntegrityViolation::LookupMismatch {
+                    number,
+                    hash: current_hash,
+                });
+            }
+            if found == 1 && number != meta.best_number {
+                violations.push(IntegrityViolation::BestPointerMismatch {
+                    number: meta.best_number,
+                    hash: meta.best_hash,
+                });
+            }
+            if let Some(expected_number) = possible_expected_number {
+                if number != expected_number {
+                    violations.push(IntegrityViolation::BrokenParentLink {
+                        number,
+                        hash: current_hash,
+                    });
+                }
+            }
+            if current_hash == meta.finalized_hash {
+                finalized_header_found = number == meta.finalized_number;
+            }
+
+            if found == meta.total_stored && current_hash != meta.oldest_stored_hash {
+                violations.push(IntegrityViolation::OldestStoredMismatch {
+                    expected: meta.oldest_stored_hash,
+                    found: current_hash,
+                });
+            }
+            possible_expected_number = if number.is_zero() {
+                None
+            } else {
+                Some(Self::previous_stored_number::<Block>(number, &meta.gaps))
+            };
+            current_hash = self.previous_stored_hash::<Block>(&header, &meta.gaps)?;
+        }
+
+        let checkpoints = match self.fetch_checkpoints::<Block>() {
+            Ok(checkpoints) => checkpoints,
+            Err((key, e)) => {
+                violations.push(IntegrityViolation::InvalidAuxValue {
+                    key,
+                    reason: e.to_string(),
+                });
+                vec![]
+            }
+        };
+
+        // Headers preceding the oldest one, which aren't checkpoints, are stored but not counted
+        if found == meta.total_stored {
+            while let Some(expected_number) = possible_expected_number {
+                let possible_header: Option<Block::Header> = self
+                    .header(BlockId::<Block>::Hash(current_hash))
+                    .ok()
+                    .flatten();
+                match possible_header {
+                    Some(header)
+                        if *header.number() == expected_number
+                            && !checkpoints.contains(&(expected_number, current_hash)) =>
+                    {
+                        found += 1;
+                        possible_expected_number = if expected_number.is_zero() {
+                            None
+                        } else {
+                            Some(Self::previous_stored_number::<Block>(
+                                expected_number,
+                                &meta.gaps,
+                            ))
+                        };
+                        current_hash = self.previous_stored_hash::<Block>(&header, &meta.gaps)?;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        if found != meta.total_stored {
+            violations.push(IntegrityViolation::TotalStoredMismatch {
+                expected: meta.total_stored,
+                found,
+            });
+        }
+
+        // Initial header is trusted, so it is treated as finalized
+        // until a header is finalized.
+        if meta.finalized_hash != Default::default()
+            && (!finalized_header_found || meta.finalized_number > meta.best_number)
+        {
+            violations.push(IntegrityViolation::FinalizedPointerMismatch {
+                number: meta.finalized_number,
+                hash: meta.finalized_hash,
+            });
+        }
+
+        for (number, hash) in checkpoints {
+            if !self.is_stored_at::<Block>(number, hash) {
+                violations.push(IntegrityViolation::MissingCheckpoint { number, hash });
+            }
+        }
+
+        match self.get_aux(LIGHT_AUTHORITY_SET_KEY)? {
+            Some(encoded_light_authority_set) => {
+                if let Err(e) =
+                    LightAuthoritySet::decode(&mut encoded_light_authority_set.as_slice())
+                {
+                    violations.push(IntegrityViolation::InvalidAuxValue {
+                        key: LIGHT_AUTHORITY_SET_KEY.to_vec(),
+                        reason: e.what().to_string(),
+                    });
+                }
+            }
+            None => violations.push(IntegrityViolation::InvalidAuxValue {
+                key: LIGHT_AUTHORITY_SET_KEY.to_vec(),
+                reason: "missing".to_string(),
+            }),
+        }
+
+        for key in &[
+            NEXT_CHANGE_IN_AUTHORITY_KEY,
+            NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY,
+        ] {
+            match fetch_authority_change::<Self, Block>(self, *key) {
+                Ok(Some(change)) => {
+                    let (hash, number) = change.block_enacting_this_change;
+                    if !self.is_stored_at::<Block>(number, hash) {
+                        violations.push(IntegrityViolation::MissingChangeSignallingHeader {
+                            key: key.to_vec(),
+                            number,
+                            hash,
+                        });
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => violations.push(IntegrityViolation::InvalidAuxValue {
+                    key: key.to_vec(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+
+        Ok(violations)
+    }
+
+    /// Copies metadata, stored headers along with their lookup entries,
+    /// checkpoints and aux values into another database. Only keys which
+    /// are known to be in use are copied, as storage isn't iterated over.
+    pub fn export_into<Block, T>(&self, target: &T) -> BlockchainResult<()>
+    where
+        Block: BlockT,
+        T: KeyValueDB,
+    {
+        let mut tx = target.transaction();
+        let possible_meta: Option<StorageMeta<NumberFor<Block>, Block::Hash>> =
+            self.fetch_meta()?;
+        if let Some(meta) = possible_meta {
+            tx_store_state_version(&mut tx, state_version(&self.db)?);
+            Self::tx_store_meta(&mut tx, &meta);
+            let mut current_hash = meta.best_hash;
+            for _ in 0..meta.total_stored {
+                let possible_header = self.header(BlockId::<Block>::Hash(current_hash))?;
+                if possible_header.is_none() {
+                    return Err(BlockchainError::Backend(format!(
+                        "FATAL: Storage inconsistency. Unable to retrieve stored block"
+                    )));
+                }
+                let header = possible_header.unwrap();
+                Self::tx_store_header::<Block>(&mut tx, &header);
+                Self::tx_store_lookup::<Block>(&mut tx, &header);
+                current_hash = self.previous_stored_hash::<Block>(&header, &meta.gaps)?;
+            }
+        }
+        for (number, hash) in self.checkpoints::<Block>()? {
+            let possible_header = self.header(BlockId::<Block>::Hash(hash))?;
+            if possible_header.is_none() {
+                return Err(BlockchainError::Backend(format!(
+                    "FATAL: Storage inconsistency. Unable to retrieve checkpoint"
+                )));
+            }
+            let header = possible_header.unwrap();
+            Self::tx_store_header::<Block>(&mut tx, &header);
+            Self::tx_store_lookup::<Block>(&mut tx, &header);
+            let key = Self::checkpoint_key::<Block>(&number);
+            if let Some(value) = self.get_aux(&key)? {
+                tx.put(AUX_COLUMN, &key, value.as_slice());
+            }
+        }
+        for key in &[
+            LIGHT_AUTHORITY_SET_KEY,
+            NEXT_CHANGE_IN_AUTHORITY_KEY,
+            NEXT_FORCED_CHANGE_IN_AUTHORITY_KEY,
+            PRUNING_POLICY_KEY,
+            LATEST_CHECKPOINT_KEY,
+            BABE_EPOCH_KEY,
+            BABE_NEXT_EPOCH_KEY,
+            AURA_AUTHORITY_SET_KEY,
+            BEEFY_VALIDATOR_SET_KEY,
+            BEEFY_NEXT_AUTHORITY_SET_KEY,
+            BEEFY_MMR_ROOT_KEY,
+        ] {
+            if let Some(value) = self.get_aux(key)? {
+                tx.put(AUX_COLUMN, key, value.as_slice());
+            }
+        }
+        target.write(tx).map_err(db_err)
+    }
+
+    /// Gives inclusive ranges of block numbers skipped by finalized
+    /// headers which were imported ahead of the best header.
+    pub fn gaps<Block>(&self) -> BlockchainResult<Vec<(NumberFor<Block>, NumberFor<Block>)>>
+    where
+        Block: BlockT,
+    {
+        let possible_meta: Option<StorageMeta<NumberFor<Block>, Block::Hash>> =
+            self.fetch_meta()?;
+        Ok(possible_meta.map_or(vec![], |meta| meta.gaps))
+    }
+
+    /// Gives number of headers stored at the moment along with
+    /// hash of the oldest one.
+    pub fn storage_info<Block>(&self) -> BlockchainResult<StorageInfo<Block>>
+    where
+        Block: BlockT,
+    {
+        let possible_meta: Option<StorageMeta<NumberFor<Block>, Block::Hash>> =
+            self.fetch_meta()?;
+        let checkpoints = self.checkpoints::<Block>()?;
+        Ok(possible_meta.map_or(
+            StorageInfo {
+                total_stored: 0,
+                oldest_stored_hash: Default::default(),
+                checkpoints: vec![],
+            },
+            |meta| StorageInfo {
+                total_stored: meta.total_stored,
+                oldest_stored_hash: meta.oldest_stored_hash,
+                checkpoints,
+            },
+        ))
+    }
+
+    /// Deletes every stored header along with the metadata, so that
+    /// storage can be seeded again from scratch.
+    pub fn clear<Block>(&self) -> BlockchainResult<()>
+    where
+        Block: BlockT,
+    {
+        let possible_meta: Option<StorageMeta<NumberFor<Block>, Block::Hash>> =
+            self.fetch_meta()?;
+        if possible_meta.is_none() {
+            return Ok(());
+        }
+        let meta = possible_meta.unwrap();
+
+        let mut tx = self.db.transaction();
+        let mut current_hash = meta.best_hash;
+        for _ in 0..meta.total_stored {
+            let possible_header = self.header(BlockId::<Block>::Hash(current_hash))?;
+            if possible_header.is_none() {
+                break;
+            }
+            let header = possible_header.unwrap();
+            Self::tx_delete_header::<Block>(&mut tx, &current_hash);
+            Self::tx_delete_lookup::<Block>(&mut tx, header.number());
+            current_hash = self.previous_stored_hash::<Block>(&header, &meta.gaps)?;
+        }
+        for (number, hash) in self.checkpoints::<Block>()? {
+            Self::tx_delete_header::<Block>(&mut tx, &hash);
+            Self::tx_delete_lookup::<Block>(&mut tx, &number);
+            tx.delete(AUX_COLUMN, &Self::checkpoint_key::<Block>(&number));
+        }
+        tx.delete(AUX_COLUMN, LATEST_CHECKPOINT_KEY);
+        tx.delete(META_COLUMN, META_KEY);
+        self.db.write(tx).map_err(db_err)
+    }
+
     fn header_hash_to_id<Block>(hash: &Block::Hash) -> Vec<u8>
     where
         Block: BlockT,
@@ -137,7 +794,6 @@ impl Storage {
             BlockId::Hash(h) => Ok(Some(Self::header_hash_to_id::<Block>(&h))),
             BlockId::Number(n) => {
                 let data = self
-                    .data
                     .db
                     .get(LOOKUP_COLUMN, n.encode().as_slice())
                     .map_err(db_err)?;
@@ -155,7 +811,6 @@ impl Storage {
         Block: BlockT,
     {
         let data = self
-            .data
             .db
             .get(LOOKUP_COLUMN, number.encode().as_slice())
             .map_err(db_err)?;
@@ -170,19 +825,22 @@ impl Storage {
     }
 }
 
-impl AuxStore for Storage {
+impl<D> AuxStore for Storage<D>
+where
+    D: KeyValueDB,
+{
     fn insert_aux<
         'a,
         'b: 'a,
         'c: 'a,
         I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
-        D: IntoIterator<Item = &'a &'b [u8]>,
+        R: IntoIterator<Item = &'a &'b [u8]>,
     >(
         &self,
         insert: I,
-        delete: D,
+        delete: R,
     ) -> BlockchainResult<()> {
-        let mut tx = self.data.db.transaction();
+        let mut tx = self.db.transaction();
         for (k, v) in insert {
             tx.put(AUX_COLUMN, *k, *v);
         }
@@ -191,17 +849,18 @@ impl AuxStore for Storage {
             tx.delete(AUX_COLUMN, *k)
         }
 
-        self.data.db.write(tx).map_err(db_err)
+        self.db.write(tx).map_err(db_err)
     }
 
     fn get_aux(&self, key: &[u8]) -> BlockchainResult<Option<Vec<u8>>> {
-        self.data.db.get(AUX_COLUMN, key).map_err(db_err)
+        self.db.get(AUX_COLUMN, key).map_err(db_err)
     }
 }
 
-impl<Block> HeaderBackend<Block> for Storage
+impl<Block, D> HeaderBackend<Block> for Storage<D>
 where
     Block: BlockT,
+    D: KeyValueDB,
 {
     fn header(&self, id: BlockId<Block>) -> BlockchainResult<Option<Block::Header>> {
         let possible_header_key = self.id(id)?;
@@ -210,7 +869,6 @@ where
         } else {
             let header_key = possible_header_key.unwrap();
             let possible_encoded_header = self
-                .data
                 .db
                 .get(HEADER_COLUMN, header_key.as_slice())
                 .map_err(db_err)?;
@@ -282,19 +940,21 @@ where
     }
 }
 
-impl<Block> StorageT<Block> for Storage
+impl<Block, D> StorageT<Block> for Storage<D>
 where
     Block: BlockT,
+    D: KeyValueDB,
 {
     /// Store new header. Should refuse to revert any finalized blocks.
     ///
     /// Takes new authorities, the leaf state of the new block, and
     /// any auxiliary storage updates to place in the same operation.
     fn import_header(&self, header: Block::Header, state: NewBlockState) -> BlockchainResult<()> {
-        assert!(
-            state.is_best(),
-            "Since, we are only following one fork block state must need to be best"
-        );
+        if state == NewBlockState::Normal {
+            return Err(BlockchainError::Backend(
+                "Since, we are only following one fork block state must need to be best".into(),
+            ));
+        }
 
         let possible_meta = self.fetch_meta()?;
         let mut meta: StorageMeta<NumberFor<Block>, Block::Hash> = if possible_meta.is_none() {
@@ -306,55 +966,20 @@ where
                 genesis_hash: Default::default(),
                 total_stored: 0,
                 oldest_stored_hash: Default::default(),
+                gaps: vec![],
             }
         } else {
             possible_meta.unwrap()
         };
 
-        let mut tx = self.data.db.transaction();
-
-        // We need to go down this in-efficient route, because
-        // to have a double linked list of headers require more storage and
-        // memory which we don't have.
-        // So, we need to backtrack every time we are above the limit.
-        if meta.total_stored >= self.max_headers_allowed_to_store {
-            let mut current_hash = meta.best_hash;
-            let amount_of_headers_to_backtrack = self.max_headers_allowed_to_store - 1;
-            let amount_of_headers_to_delete =
-                (meta.total_stored - self.max_headers_allowed_to_store) + 1;
-            // First backtrack to the newest header we need to delete
-            for _ in 0..amount_of_headers_to_backtrack {
-                let possible_header = self.header(BlockId::<Block>::Hash(current_hash))?;
-                if possible_header.is_none() {
-                    return Err(BlockchainError::Backend(format!(
-                        "FATAL: Storage inconsistency. Unable to retrieve stored block"
-                    )));
-                }
-                let header = possible_header.unwrap();
-                meta.oldest_stored_hash = current_hash;
-                current_hash = *header.parent_hash();
-            }
-            // Now, Let's delete newest header and all headers older than newest header
-            for _ in 0..amount_of_headers_to_delete {
-                let possible_header = self.header(BlockId::<Block>::Hash(current_hash))?;
-                if possible_header.is_none() {
-                    return Err(BlockchainError::Backend(format!(
-                        "FATAL: Storage inconsistency. Unable to retrieve stored block"
-                    )));
-                }
-                Self::tx_delete_header::<Block>(&mut tx, &current_hash);
-                meta.total_stored -= 1;
-                let header = possible_header.unwrap();
-                current_hash = *header.parent_hash();
-            }
-        }
-
         let possible_header = self.header(BlockId::<Block>::Hash(header.hash()))?;
         if possible_header.is_some() {
             // We have already imported this block
             return Ok(());
         }
 
+        let mut tx = self.db.transaction();
+
         let first_imported_header = meta.best_hash == Default::default();
 
         // We need to check if this is child of last best header
@@ -366,30 +991,122 @@ where
                 )));
             }
             let parent_header = possible_parent_header.unwrap();
-            if *header.parent_hash() != parent_header.hash()
-                || header.number() <= parent_header.number()
-            {
-                return Err(BlockchainError::NotInFinalizedChain);
-            }
-            if *header.number() != meta.best_number + One::one() {
-                return Err(BlockchainError::NonSequentialImport(format!(
-                    "to be imported block need to be child of last best block or first block itself. Expected block number: {}. Got: {}",
-                    meta.best_number + One::one(),
-                    *header.number()
-                )));
-            }
+            if state == NewBlockState::Final && *header.parent_hash() != parent_header.hash() {
+                // Finalized header is allowed to skip ahead of the best header,
+                // but headers which are not finalized can't be linked to it anymore.
+                let first_discarded_number =
+                    self.tx_discard_non_finalized::<Block>(&mut tx, &mut meta)?;
+                if *header.number() <= first_discarded_number {
+                    return Err(BlockchainError::NotInFinalizedChain);
+                }
+                if meta.total_stored == 0 {
+                    meta.oldest_stored_hash = header.hash();
+                } else {
+                    meta.gaps
+                        .push((first_discarded_number, *header.number() - One::one()));
+                }
+            } else {
+                if *header.parent_hash() != parent_header.hash()
+                    || header.number() <= parent_header.number()
+                {
+                    return Err(BlockchainError::NotInFinalizedChain);
+                }
+                if *header.number() != meta.best_number + One::one() {
+                    return Err(BlockchainError::NonSequentialImport(format!(
+                        "to be imported block need to be child of last best block or first block itself. Expected block number: {}. Got: {}",
+                        meta.best_number + One::one(),
+                        *header.number()
+                    )));
+                }
+            }
         } else {
             meta.genesis_hash = header.hash();
             meta.oldest_stored_hash = header.hash();
+            // Database is written from scratch in the current format
+            tx_store_state_version(&mut tx, CURRENT_STATE_VERSION);
+        }
+
+        // Oldest header is looked up directly, and the one following it through
+        // lookup column, so that pruning costs the same regardless of capacity.
+        if meta.total_stored >= self.max_headers_allowed_to_store {
+            let amount_of_headers_to_delete =
+                (meta.total_stored - self.max_headers_allowed_to_store) + 1;
+            let pinned_hashes =
+                self.pinned_hashes::<Block>(&meta, state == NewBlockState::Final)?;
+            let pruning_policy = self.pruning_policy()?;
+            // Loaded only once a checkpoint is pruned
+            let mut possible_latest_checkpoint: Option<Option<NumberFor<Block>>> = None;
+            let mut last_deleted_number = Zero::zero();
+            for _ in 0..amount_of_headers_to_delete {
+                if pinned_hashes.contains(&meta.oldest_stored_hash) {
+                    // Pruning it would leave finalization or authority set
+                    // change tracking pointing at missing header.
+                    return Err(BlockchainError::StorageFull(format!(
+                        "oldest stored header {} need to be kept, as it is the last finalized header or it signals pending authority set change",
+                        meta.oldest_stored_hash
+                    )));
+                }
+                let possible_header =
+                    self.header(BlockId::<Block>::Hash(meta.oldest_stored_hash))?;
+                if possible_header.is_none() {
+                    return Err(BlockchainError::Backend(format!(
+                        "FATAL: Storage inconsistency. Unable to retrieve stored block"
+                    )));
+                }
+                let oldest_header = possible_header.unwrap();
+                if Self::is_checkpoint::<Block>(&oldest_header, &pruning_policy) {
+                    // Checkpoint keeps its header and lookup entries,
+                    // but it isn't counted as stored anymore.
+                    let previous_number = match possible_latest_checkpoint {
+                        Some(latest_checkpoint) => latest_checkpoint,
+                        None => self.latest_checkpoint::<Block>()?,
+                    };
+                    Self::tx_store_checkpoint::<Block>(
+                        &mut tx,
+                        oldest_header.number(),
+                        meta.oldest_stored_hash,
+                        previous_number,
+                    );
+                    possible_latest_checkpoint = Some(Some(*oldest_header.number()));
+                } else {
+                    Self::tx_delete_header::<Block>(&mut tx, &meta.oldest_stored_hash);
+                    Self::tx_delete_lookup::<Block>(&mut tx, oldest_header.number());
+                }
+                meta.total_stored -= 1;
+                last_deleted_number = *oldest_header.number();
+                if meta.total_stored == 0 {
+                    // Header being imported is the only one left
+                    meta.oldest_stored_hash = header.hash();
+                    break;
+                }
+
+                let next_stored_number =
+                    Self::next_stored_number::<Block>(last_deleted_number, &meta.gaps);
+                let possible_next_stored_hash = self.header_hash::<Block>(next_stored_number)?;
+                if possible_next_stored_hash.is_none() {
+                    return Err(BlockchainError::Backend(format!(
+                        "FATAL: Storage inconsistency. Unable to retrieve stored block"
+                    )));
+                }
+                meta.oldest_stored_hash = possible_next_stored_hash.unwrap();
+            }
+            // Gaps below the oldest stored header are not gaps anymore
+            meta.gaps
+                .retain(|(start, _)| *start > last_deleted_number + One::one());
         }
 
         meta.total_stored += 1;
         meta.best_hash = header.hash();
         meta.best_number = *header.number();
+        if state == NewBlockState::Final {
+            meta.finalized_hash = header.hash();
+            meta.finalized_number = *header.number();
+        }
 
         Self::tx_store_meta(&mut tx, &meta);
         Self::tx_store_header::<Block>(&mut tx, &header);
-        self.data.db.write(tx).map_err(db_err)
+        Self::tx_store_lookup::<Block>(&mut tx, &header);
+        self.db.write(tx).map_err(db_err)
     }
 
     /// Set an existing block as new best block.
@@ -444,9 +1161,10 @@ where
     }
 }
 
-impl<Block> HeaderMetadata<Block> for Storage
+impl<Block, D> HeaderMetadata<Block> for Storage<D>
 where
     Block: BlockT,
+    D: KeyValueDB,
 {
     type Error = BlockchainError;
 
@@ -469,16 +1187,92 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::common::traits::aux_store::AuxStore;
     use crate::common::traits::header_backend::HeaderBackend;
     use crate::common::traits::storage::Storage as StorageT;
+    use crate::common::types::integrity_violation::IntegrityViolation;
+    use crate::common::types::light_authority_set::LightAuthoritySet;
     use crate::common::types::new_block_state::NewBlockState;
-    use crate::db::{create, Data};
-    use crate::genesis::GenesisData;
-    use crate::storage::Storage;
+    use crate::common::types::next_change_in_authority::NextChangeInAuthority;
+    use crate::common::types::pruning_policy::PruningPolicy;
+    use crate::common::types::storage_info::StorageInfo;
+    use crate::common::utils::{LIGHT_AUTHORITY_SET_KEY, NEXT_CHANGE_IN_AUTHORITY_KEY};
+    use crate::db::{create, DB};
+    use crate::storage::{Storage, HEADER_COLUMN, LOOKUP_COLUMN};
     use crate::types::{Block, Header};
+    use kvdb::{DBTransaction, DBValue, KeyValueDB};
     use parity_scale_codec::Encode;
+    use parity_util_mem::{MallocSizeOf, MallocSizeOfOps};
     use sp_api::BlockId;
+    use sp_finality_grandpa::{ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
     use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, One};
+    use sp_runtime::DigestItem;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// In-memory database which counts reads, as a measure of cost of
+    /// storage operations independent of the machine running tests.
+    #[derive(Clone)]
+    struct CountingDB {
+        db: DB,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl CountingDB {
+        fn new(db: DB) -> Self {
+            Self {
+                db,
+                reads: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn reads(&self) -> usize {
+            self.reads.load(Ordering::SeqCst)
+        }
+    }
+
+    impl MallocSizeOf for CountingDB {
+        fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+            self.db.size_of(ops)
+        }
+    }
+
+    impl KeyValueDB for CountingDB {
+        fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.db.get(col, key)
+        }
+
+        fn get_by_prefix(&self, col: u32, prefix: &[u8]) -> Option<Box<[u8]>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.db.get_by_prefix(col, prefix)
+        }
+
+        fn write_buffered(&self, transaction: DBTransaction) {
+            self.db.write_buffered(transaction)
+        }
+
+        fn flush(&self) -> io::Result<()> {
+            self.db.flush()
+        }
+
+        fn iter<'a>(&'a self, col: u32) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+            self.db.iter(col)
+        }
+
+        fn iter_from_prefix<'a>(
+            &'a self,
+            col: u32,
+            prefix: &'a [u8],
+        ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+            self.db.iter_from_prefix(col, prefix)
+        }
+
+        fn restore(&self, new_db: &str) -> io::Result<()> {
+            self.db.restore(new_db)
+        }
+    }
 
     fn create_next_header(header: Header) -> Header {
         let mut next_header = header.clone();
@@ -489,40 +1283,49 @@ mod tests {
 
     #[test]
     fn test_storage_init() {
-        let data = Data {
-            db: create(11),
-            genesis_data: GenesisData {},
-        };
+        let db = create(11);
 
-        let result = Storage::new(data.clone(), 2);
+        let result = Storage::new(db.clone(), 2);
         assert!(result.is_ok());
 
-        let result = Storage::new(data.clone(), 1);
+        let result = Storage::new(db.clone(), 1);
         assert!(result.is_err());
         assert_eq!(
             result.err().unwrap().to_string(),
             "Backend error: Maximum amount of blocks allowed to store need to be at least 2"
         );
 
-        let result = Storage::new(data.clone(), 0);
+        let result = Storage::new(db.clone(), 0);
         assert!(result.is_err());
         assert_eq!(
             result.err().unwrap().to_string(),
             "Backend error: Maximum amount of blocks allowed to store need to be at least 2"
         );
+
+        // We only follow one fork, so blocks not becoming best are refused
+        let storage = Storage::new(db.clone(), 2).unwrap();
+        let header = create_next_header(Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ));
+        let result = StorageT::<Block>::import_header(&storage, header, NewBlockState::Normal);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Backend error: Since, we are only following one fork block state must need to be best"
+        );
     }
 
     #[test]
     fn test_storage_space_management() {
-        let data = Data {
-            db: create(11),
-            genesis_data: GenesisData {},
-        };
+        let db = create(11);
 
         let mut produced_headers = vec![];
         let max_headers_allowed_to_store = 7;
 
-        let result = Storage::new(data.clone(), max_headers_allowed_to_store);
+        let result = Storage::new(db.clone(), max_headers_allowed_to_store);
         assert!(result.is_ok());
         let storage = result.unwrap();
 
@@ -539,14 +1342,15 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
 
-        // Let's store first max_headers_allowed_to_store number of headers
+        // Let's store first max_headers_allowed_to_store number of headers. They
+        // are imported as finalized, as the last finalized header is never pruned.
         for i in 0..max_headers_allowed_to_store {
             current_header = create_next_header(current_header.clone());
             produced_headers.push(current_header.clone());
             assert!(StorageT::<Block>::import_header(
                 &storage,
                 current_header.clone(),
-                NewBlockState::Best
+                NewBlockState::Final
             )
             .is_ok());
 
@@ -560,7 +1364,7 @@ mod tests {
             assert_eq!(meta.oldest_stored_hash, produced_headers[0].hash());
         }
 
-        let current_size = data.encode().len();
+        let current_size = db.encode().len();
         // Due to underlying DB's design there is slight drift equivalent
         // to amount of headers we are allowed to store.
         let size_drift_allowed = max_headers_allowed_to_store as usize;
@@ -575,10 +1379,10 @@ mod tests {
             assert!(StorageT::<Block>::import_header(
                 &storage,
                 current_header.clone(),
-                NewBlockState::Best
+                NewBlockState::Final
             )
             .is_ok());
-            assert!(data.encode().len() <= current_size + size_drift_allowed);
+            assert!(db.encode().len() <= current_size + size_drift_allowed);
 
             let last_header_to_be_deleted = i - max_headers_allowed_to_store;
 
@@ -618,7 +1422,7 @@ mod tests {
 
         // Now, let's check if reducing max_headers_allowed_to_store parameter reduces storage.
         let max_headers_allowed_to_store = max_headers_allowed_to_store - 3;
-        let result = Storage::new(data.clone(), max_headers_allowed_to_store);
+        let result = Storage::new(db.clone(), max_headers_allowed_to_store);
         assert!(result.is_ok());
         let storage = result.unwrap();
         current_header = create_next_header(current_header.clone());
@@ -626,13 +1430,13 @@ mod tests {
         assert!(StorageT::<Block>::import_header(
             &storage,
             current_header.clone(),
-            NewBlockState::Best
+            NewBlockState::Final
         )
         .is_ok());
-        assert!(data.encode().len() < current_size);
+        assert!(db.encode().len() < current_size);
         // Updating current size and size drift as per new max_headers_allowed_to_store
         // value.
-        let current_size = data.encode().len();
+        let current_size = db.encode().len();
         let size_drift_allowed = max_headers_allowed_to_store;
         let result = storage.fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>();
         assert!(result.is_ok());
@@ -663,7 +1467,7 @@ mod tests {
 
         // Now, let's check if increasing max_headers_allowed_to_store_parameter allows storage to grow
         let max_headers_allowed_to_store = max_headers_allowed_to_store + 3;
-        let result = Storage::new(data.clone(), max_headers_allowed_to_store);
+        let result = Storage::new(db.clone(), max_headers_allowed_to_store);
         assert!(result.is_ok());
         let storage = result.unwrap();
         current_header = create_next_header(current_header.clone());
@@ -671,11 +1475,11 @@ mod tests {
         assert!(StorageT::<Block>::import_header(
             &storage,
             current_header.clone(),
-            NewBlockState::Best
+            NewBlockState::Final
         )
         .is_ok());
         // Now, we are able to increase size beyond our previous size.
-        assert!(data.encode().len() > current_size + size_drift_allowed as usize);
+        assert!(db.encode().len() > current_size + size_drift_allowed as usize);
         let result = storage.fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>();
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -698,4 +1502,585 @@ mod tests {
             assert!(result.unwrap().is_some());
         }
     }
+
+    #[test]
+    fn test_storage_pruning_cost_is_flat_at_capacity() {
+        let db = CountingDB::new(create(11));
+        let max_headers_allowed_to_store = 10_000;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        assert!(StorageT::<Block>::import_header(
+            &storage,
+            current_header.clone(),
+            NewBlockState::Final
+        )
+        .is_ok());
+
+        let mut reads_below_capacity = vec![];
+        let mut reads_at_capacity = vec![];
+        for i in 1..(max_headers_allowed_to_store + 100) {
+            current_header = create_next_header(current_header.clone());
+            let reads_before_import = db.reads();
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                current_header.clone(),
+                NewBlockState::Final
+            )
+            .is_ok());
+            let reads = db.reads() - reads_before_import;
+            if i < max_headers_allowed_to_store {
+                reads_below_capacity.push(reads);
+            } else {
+                reads_at_capacity.push(reads);
+            }
+        }
+
+        // Pruning reads the oldest header, looks up the one following it and
+        // reads pending authority set changes along with pruning policy, no
+        // matter how many headers are stored.
+        let max_reads_below_capacity = *reads_below_capacity.iter().max().unwrap();
+        assert!(reads_at_capacity
+            .iter()
+            .all(|reads| *reads == max_reads_below_capacity + 5));
+
+        // Retaining pruned header as checkpoint only reads number of the latest
+        // checkpoint on top, no matter how many checkpoints are retained.
+        assert!(storage
+            .set_pruning_policy(&PruningPolicy {
+                checkpoint_authority_set_changes: false,
+                checkpoint_interval: 2,
+            })
+            .is_ok());
+        let mut reads_retaining_checkpoints = vec![];
+        for _ in 0..200 {
+            current_header = create_next_header(current_header.clone());
+            let reads_before_import = db.reads();
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                current_header.clone(),
+                NewBlockState::Final
+            )
+            .is_ok());
+            let reads = db.reads() - reads_before_import;
+            let pruned_number = *current_header.number() - max_headers_allowed_to_store as u32;
+            if pruned_number % 2 == 0 {
+                reads_retaining_checkpoints.push(reads);
+            } else {
+                assert_eq!(reads, max_reads_below_capacity + 5);
+            }
+        }
+        assert_eq!(reads_retaining_checkpoints.len(), 100);
+        assert!(reads_retaining_checkpoints
+            .iter()
+            .all(|reads| *reads == max_reads_below_capacity + 6));
+        assert_eq!(storage.checkpoints::<Block>().unwrap().len(), 100);
+        assert!(storage.check_integrity::<Block>().unwrap().is_empty());
+
+        let storage_info = storage.storage_info::<Block>().unwrap();
+        assert_eq!(storage_info.total_stored, max_headers_allowed_to_store);
+        let oldest_stored_header: Header = storage
+            .header(BlockId::<Block>::Hash(storage_info.oldest_stored_hash))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            *oldest_stored_header.number(),
+            *current_header.number() - max_headers_allowed_to_store as u32 + 1
+        );
+        assert!(storage
+            .header(BlockId::<Block>::Number(*oldest_stored_header.number() - 1))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_storage_number_lookup() {
+        let db = create(11);
+
+        let mut produced_headers = vec![];
+        let max_headers_allowed_to_store = 4;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        for _ in 0..(max_headers_allowed_to_store * 3) {
+            current_header = create_next_header(current_header.clone());
+            produced_headers.push(current_header.clone());
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                current_header.clone(),
+                NewBlockState::Final
+            )
+            .is_ok());
+        }
+
+        let first_stored_header_index =
+            produced_headers.len() - max_headers_allowed_to_store as usize;
+        for (index, header) in produced_headers.iter().enumerate() {
+            let result = HeaderBackend::<Block>::hash(&storage, header.number);
+            assert!(result.is_ok());
+            let result_header =
+                HeaderBackend::<Block>::header(&storage, BlockId::<Block>::Number(header.number));
+            assert!(result_header.is_ok());
+
+            if index < first_stored_header_index {
+                // Pruned headers should be removed from lookup as well
+                assert!(result.unwrap().is_none());
+                assert!(result_header.unwrap().is_none());
+            } else {
+                assert_eq!(result.unwrap(), Some(header.hash()));
+                assert_eq!(result_header.unwrap().as_ref(), Some(header));
+            }
+        }
+
+        // Header which hasn't been imported yet
+        let result = HeaderBackend::<Block>::hash(&storage, current_header.number + 1);
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_storage_finalized_header_skipping_ahead() {
+        let db = create(11);
+
+        let max_headers_allowed_to_store = 3;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+
+        let mut produced_headers = vec![];
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        for _ in 0..7 {
+            current_header = create_next_header(current_header.clone());
+            produced_headers.push(current_header.clone());
+        }
+
+        for header in &produced_headers[..2] {
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                header.clone(),
+                NewBlockState::Best
+            )
+            .is_ok());
+        }
+
+        // Finalized header need to be ahead of the headers which are not finalized
+        let mut conflicting_header = produced_headers[1].clone();
+        conflicting_header.parent_hash = Default::default();
+        let result =
+            StorageT::<Block>::import_header(&storage, conflicting_header, NewBlockState::Final);
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Potential long-range attack: block not in finalized chain."
+        );
+
+        // Skipping ahead discards header which is not finalized and records a gap
+        assert!(StorageT::<Block>::import_header(
+            &storage,
+            produced_headers[4].clone(),
+            NewBlockState::Final
+        )
+        .is_ok());
+        let meta = storage
+            .fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.total_stored, 2);
+        assert_eq!(meta.best_hash, produced_headers[4].hash());
+        assert_eq!(meta.finalized_hash, produced_headers[4].hash());
+        assert_eq!(meta.oldest_stored_hash, produced_headers[0].hash());
+        assert_eq!(storage.gaps::<Block>().unwrap(), vec![(3, 5)]);
+        let result = HeaderBackend::<Block>::header(
+            &storage,
+            BlockId::<Block>::Hash(produced_headers[1].hash()),
+        );
+        assert!(result.unwrap().is_none());
+
+        // Pruning steps over the gap, which goes away with the header preceding it
+        for header in &produced_headers[5..] {
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                header.clone(),
+                NewBlockState::Best
+            )
+            .is_ok());
+        }
+        let meta = storage
+            .fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.total_stored, max_headers_allowed_to_store);
+        assert_eq!(meta.oldest_stored_hash, produced_headers[4].hash());
+        assert!(storage.gaps::<Block>().unwrap().is_empty());
+        let result = HeaderBackend::<Block>::header(
+            &storage,
+            BlockId::<Block>::Hash(produced_headers[0].hash()),
+        );
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_storage_keeps_last_finalized_header() {
+        let db = create(11);
+
+        let max_headers_allowed_to_store = 3;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+
+        let mut produced_headers = vec![];
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        for _ in 0..4 {
+            current_header = create_next_header(current_header.clone());
+            produced_headers.push(current_header.clone());
+        }
+
+        for header in &produced_headers[..3] {
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                header.clone(),
+                NewBlockState::Best
+            )
+            .is_ok());
+        }
+
+        // Initial header is trusted, so it is kept as the last finalized header
+        let result = StorageT::<Block>::import_header(
+            &storage,
+            produced_headers[3].clone(),
+            NewBlockState::Best,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            format!("Storage is full: oldest stored header {} need to be kept, as it is the last finalized header or it signals pending authority set change", produced_headers[0].hash())
+        );
+        let meta = storage
+            .fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.total_stored, max_headers_allowed_to_store);
+        assert_eq!(meta.best_hash, produced_headers[2].hash());
+        assert_eq!(meta.oldest_stored_hash, produced_headers[0].hash());
+
+        assert!(StorageT::<Block>::finalize_header(
+            &storage,
+            BlockId::<Block>::Hash(produced_headers[0].hash())
+        )
+        .is_ok());
+        assert!(StorageT::<Block>::import_header(
+            &storage,
+            produced_headers[3].clone(),
+            NewBlockState::Best
+        )
+        .is_err());
+
+        // Once a newer header is finalized, the previous one can be pruned
+        assert!(StorageT::<Block>::finalize_header(
+            &storage,
+            BlockId::<Block>::Hash(produced_headers[1].hash())
+        )
+        .is_ok());
+        assert!(StorageT::<Block>::import_header(
+            &storage,
+            produced_headers[3].clone(),
+            NewBlockState::Best
+        )
+        .is_ok());
+        let meta = storage
+            .fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.total_stored, max_headers_allowed_to_store);
+        assert_eq!(meta.best_hash, produced_headers[3].hash());
+        assert_eq!(meta.oldest_stored_hash, produced_headers[1].hash());
+    }
+
+    #[test]
+    fn test_storage_keeps_header_signalling_pending_authority_set_change() {
+        let db = create(11);
+
+        let max_headers_allowed_to_store = 3;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+
+        let mut produced_headers = vec![];
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        for _ in 0..5 {
+            current_header = create_next_header(current_header.clone());
+            produced_headers.push(current_header.clone());
+        }
+
+        let change = NextChangeInAuthority::<Block>::new(
+            produced_headers[1].number + 5,
+            (produced_headers[1].hash(), produced_headers[1].number),
+            ScheduledChange {
+                next_authorities: vec![],
+                delay: 5,
+            },
+        );
+        assert!(storage
+            .insert_aux(
+                &[(NEXT_CHANGE_IN_AUTHORITY_KEY, change.encode().as_slice())],
+                &[]
+            )
+            .is_ok());
+
+        for header in &produced_headers[..4] {
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                header.clone(),
+                NewBlockState::Final
+            )
+            .is_ok());
+        }
+        let meta = storage
+            .fetch_meta::<NumberFor<Block>, <Block as BlockT>::Hash>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.oldest_stored_hash, produced_headers[1].hash());
+
+        let result = StorageT::<Block>::import_header(
+            &storage,
+            produced_headers[4].clone(),
+            NewBlockState::Final,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            format!("Storage is full: oldest stored header {} need to be kept, as it is the last finalized header or it signals pending authority set change", produced_headers[1].hash())
+        );
+        let result = HeaderBackend::<Block>::header(
+            &storage,
+            BlockId::<Block>::Hash(produced_headers[4].hash()),
+        );
+        assert!(result.unwrap().is_none());
+
+        // Header can be pruned once the change it signals is enacted
+        assert!(storage
+            .insert_aux(&[], &[NEXT_CHANGE_IN_AUTHORITY_KEY])
+            .is_ok());
+        assert!(StorageT::<Block>::import_header(
+            &storage,
+            produced_headers[4].clone(),
+            NewBlockState::Final
+        )
+        .is_ok());
+        let result = HeaderBackend::<Block>::header(
+            &storage,
+            BlockId::<Block>::Hash(produced_headers[1].hash()),
+        );
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_storage_retains_checkpoints() {
+        let db = create(11);
+
+        let max_headers_allowed_to_store = 2;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+        assert!(storage
+            .set_pruning_policy(&PruningPolicy {
+                checkpoint_authority_set_changes: true,
+                checkpoint_interval: 3,
+            })
+            .is_ok());
+
+        // Headers numbered from 2 to 9, with header 5 signalling authority set change
+        let mut produced_headers = vec![];
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        for _ in 0..8 {
+            current_header = create_next_header(current_header.clone());
+            current_header.digest = Default::default();
+            if current_header.number == 5 {
+                current_header.digest.push(DigestItem::Consensus(
+                    GRANDPA_ENGINE_ID,
+                    ConsensusLog::<NumberFor<Block>>::ScheduledChange(ScheduledChange {
+                        next_authorities: vec![],
+                        delay: 0,
+                    })
+                    .encode(),
+                ));
+            }
+            produced_headers.push(current_header.clone());
+        }
+
+        for header in &produced_headers {
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                header.clone(),
+                NewBlockState::Final
+            )
+            .is_ok());
+        }
+
+        let expected_checkpoints = vec![
+            (3, produced_headers[1].hash()),
+            (5, produced_headers[3].hash()),
+            (6, produced_headers[4].hash()),
+        ];
+        assert_eq!(
+            storage.checkpoints::<Block>().unwrap(),
+            expected_checkpoints
+        );
+        let storage_info = storage.storage_info::<Block>().unwrap();
+        assert_eq!(storage_info.total_stored, max_headers_allowed_to_store);
+        assert_eq!(storage_info.oldest_stored_hash, produced_headers[6].hash());
+        assert_eq!(storage_info.checkpoints, expected_checkpoints);
+
+        // Checkpoints remain available by number and hash
+        for (index, header) in produced_headers.iter().enumerate() {
+            let result =
+                HeaderBackend::<Block>::header(&storage, BlockId::<Block>::Number(header.number));
+            if [1, 3, 4, 6, 7].contains(&index) {
+                assert_eq!(result.unwrap().as_ref(), Some(header));
+            } else {
+                assert!(result.unwrap().is_none());
+            }
+        }
+
+        assert!(storage.clear::<Block>().is_ok());
+        assert!(storage.checkpoints::<Block>().unwrap().is_empty());
+        for (number, _) in &expected_checkpoints {
+            let key = Storage::<DB>::checkpoint_key::<Block>(number);
+            assert!(storage.get_aux(&key).unwrap().is_none());
+        }
+        for header in &produced_headers {
+            let result =
+                HeaderBackend::<Block>::header(&storage, BlockId::<Block>::Hash(header.hash()));
+            assert!(result.unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_storage_integrity_check() {
+        let db = create(11);
+
+        let max_headers_allowed_to_store = 5;
+        let storage = Storage::new(db.clone(), max_headers_allowed_to_store).unwrap();
+
+        // Nothing is stored yet
+        assert_eq!(
+            storage.check_integrity::<Block>().unwrap(),
+            vec![IntegrityViolation::MissingMeta]
+        );
+
+        let mut produced_headers = vec![];
+        let mut current_header = Header::new(
+            One::one(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        for _ in 0..4 {
+            current_header = create_next_header(current_header.clone());
+            produced_headers.push(current_header.clone());
+            assert!(StorageT::<Block>::import_header(
+                &storage,
+                current_header.clone(),
+                NewBlockState::Final
+            )
+            .is_ok());
+        }
+
+        assert_eq!(
+            storage.check_integrity::<Block>().unwrap(),
+            vec![IntegrityViolation::InvalidAuxValue {
+                key: LIGHT_AUTHORITY_SET_KEY.to_vec(),
+                reason: "missing".to_string(),
+            }]
+        );
+        assert!(storage
+            .insert_aux(
+                &[(
+                    LIGHT_AUTHORITY_SET_KEY,
+                    LightAuthoritySet::default().encode().as_slice()
+                )],
+                &[]
+            )
+            .is_ok());
+        assert!(storage.check_integrity::<Block>().unwrap().is_empty());
+
+        // Corrupt lookup entry of one header and remove another one, which
+        // signals pending authority set change.
+        let mut transaction = db.transaction();
+        transaction.delete(
+            LOOKUP_COLUMN,
+            produced_headers[2].number.encode().as_slice(),
+        );
+        transaction.delete(
+            HEADER_COLUMN,
+            produced_headers[1].hash().encode().as_slice(),
+        );
+        assert!(db.write(transaction).is_ok());
+        let change = NextChangeInAuthority::<Block>::new(
+            produced_headers[1].number + 5,
+            (produced_headers[1].hash(), produced_headers[1].number),
+            ScheduledChange {
+                next_authorities: vec![],
+                delay: 5,
+            },
+        );
+        assert!(storage
+            .insert_aux(
+                &[(NEXT_CHANGE_IN_AUTHORITY_KEY, change.encode().as_slice())],
+                &[]
+            )
+            .is_ok());
+
+        assert_eq!(
+            storage.check_integrity::<Block>().unwrap(),
+            vec![
+                IntegrityViolation::LookupMismatch {
+                    number: produced_headers[2].number,
+                    hash: produced_headers[2].hash(),
+                },
+                IntegrityViolation::MissingHeader {
+                    hash: produced_headers[1].hash(),
+                },
+                IntegrityViolation::TotalStoredMismatch {
+                    expected: 4,
+                    found: 2,
+                },
+                IntegrityViolation::MissingChangeSignallingHeader {
+                    key: NEXT_CHANGE_IN_AUTHORITY_KEY.to_vec(),
+                    number: produced_headers[1].number,
+                    hash: produced_headers[1].hash(),
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,112 @@
+use crate::common::types::blockchain_error::BlockchainError;
+use crate::common::types::blockchain_result::BlockchainResult;
+use sp_core::Hasher;
+use sp_trie::{read_trie_value, Layout, MemoryDB, StorageProof};
+
+/// Reads value stored under `key` in the state trie identified by `state_root`.
+/// Only trie nodes contained in the proof are used for the lookup, so the proof
+/// needs to be same as returned by `state_getReadProof` rpc call of substrate node.
+///
+/// Returns `None` if proof shows that key doesn't exist in the trie, and error if
+/// proof does not contain all the nodes required to reach the key.
+pub fn read_proof_value<H>(
+    state_root: &H::Out,
+    proof: Vec<Vec<u8>>,
+    key: &[u8],
+) -> BlockchainResult<Option<Vec<u8>>>
+where
+    H: Hasher,
+{
+    let db: MemoryDB<H> = StorageProof::new(proof).into_memory_db();
+    read_trie_value::<Layout<H>, _>(&db, state_root, key)
+        .map_err(|e| BlockchainError::InvalidStorageProof(format!("{:?}", e)))
+}
+
+/// Verifies that proof proves existence of `expected_value` under `key`,
+/// or absence of `key` if `expected_value` is `None`.
+pub fn verify_read_proof<H>(
+    state_root: &H::Out,
+    proof: Vec<Vec<u8>>,
+    key: &[u8],
+    expected_value: Option<&[u8]>,
+) -> BlockchainResult<bool>
+where
+    H: Hasher,
+{
+    let possible_value = read_proof_value::<H>(state_root, proof, key)?;
+    Ok(possible_value.as_ref().map(|v| v.as_slice()) == expected_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage_proof::{read_proof_value, verify_read_proof};
+    use sp_core::H256;
+    use sp_runtime::traits::BlakeTwo256;
+    use sp_trie::{Layout, MemoryDB, TrieDBMut, TrieMut};
+
+    fn create_trie(entries: &[(&[u8], &[u8])]) -> (H256, Vec<Vec<u8>>) {
+        let mut db = MemoryDB::<BlakeTwo256>::default();
+        let mut root = H256::default();
+        {
+            let mut trie = TrieDBMut::<Layout<BlakeTwo256>>::new(&mut db, &mut root);
+            for (key, value) in entries {
+                trie.insert(key, value).unwrap();
+            }
+        }
+        // Every live node of the trie is a superset of nodes required
+        // to prove any key, which is good enough for a proof.
+        let proof = db
+            .drain()
+            .into_iter()
+            .filter(|(_, (_, rc))| *rc > 0)
+            .map(|(_, (node, _))| node)
+            .collect();
+        (root, proof)
+    }
+
+    #[test]
+    fn test_verify_membership() {
+        let (root, proof) =
+            create_trie(&[(b"alice", b"100"), (b"bob", b"200"), (b"charlie", b"300")]);
+
+        let result = read_proof_value::<BlakeTwo256>(&root, proof.clone(), b"bob");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), Some(b"200".to_vec()));
+
+        let result = verify_read_proof::<BlakeTwo256>(&root, proof.clone(), b"alice", Some(b"100"));
+        assert!(result.is_ok());
+        assert!(result.unwrap());
+
+        // Value mismatch
+        let result = verify_read_proof::<BlakeTwo256>(&root, proof.clone(), b"alice", Some(b"200"));
+        assert!(result.is_ok());
+        assert!(!result.unwrap());
+    }
+
+    #[test]
+    fn test_verify_non_membership() {
+        let (root, proof) = create_trie(&[(b"alice", b"100"), (b"bob", b"200")]);
+
+        let result = verify_read_proof::<BlakeTwo256>(&root, proof.clone(), b"dave", None);
+        assert!(result.is_ok());
+        assert!(result.unwrap());
+
+        // Key exists, so non membership can't be proven
+        let result = verify_read_proof::<BlakeTwo256>(&root, proof.clone(), b"bob", None);
+        assert!(result.is_ok());
+        assert!(!result.unwrap());
+    }
+
+    #[test]
+    fn test_verify_with_incomplete_proof() {
+        let (root, _) = create_trie(&[(b"alice", b"100"), (b"bob", b"200")]);
+        let (_, other_proof) = create_trie(&[(b"alice", b"100")]);
+
+        let result = read_proof_value::<BlakeTwo256>(&root, vec![], b"alice");
+        assert!(result.is_err());
+
+        // Proof from different state is useless for this state root
+        let result = read_proof_value::<BlakeTwo256>(&root, other_proof, b"alice");
+        assert!(result.is_err());
+    }
+}
@@ -1,9 +1,26 @@
-use sp_runtime::{traits::BlakeTwo256, OpaqueExtrinsic};
+use sp_runtime::traits::{BlakeTwo256, Keccak256};
+use sp_runtime::{generic, OpaqueExtrinsic};
 
 pub type BlockNumber = u32;
 
-pub type Header = sp_runtime::generic::Header<BlockNumber, BlakeTwo256>;
+pub type Header = generic::Header<BlockNumber, BlakeTwo256>;
 
-pub type Block = sp_runtime::generic::Block<Header, OpaqueExtrinsic>;
+pub type Block = generic::Block<Header, OpaqueExtrinsic>;
 
-pub type SignedBlock = sp_runtime::generic::SignedBlock<Block>;
+pub type SignedBlock = generic::SignedBlock<Block>;
+
+/// Header of a chain with `u64` block numbers.
+pub type U64Header = generic::Header<u64, BlakeTwo256>;
+
+pub type U64Block = generic::Block<U64Header, OpaqueExtrinsic>;
+
+/// Header of a chain hashing headers and state trie with Keccak256.
+pub type KeccakHeader = generic::Header<BlockNumber, Keccak256>;
+
+pub type KeccakBlock = generic::Block<KeccakHeader, OpaqueExtrinsic>;
+
+/// Header of a chain with `u64` block numbers, hashing headers and
+/// state trie with Keccak256.
+pub type U64KeccakHeader = generic::Header<u64, Keccak256>;
+
+pub type U64KeccakBlock = generic::Block<U64KeccakHeader, OpaqueExtrinsic>;
@@ -3,20 +3,24 @@ use crate::common::traits::header_backend::HeaderBackend;
 use crate::common::traits::verifier::Verifier;
 use crate::common::types::block_import_params::BlockImportParams;
 use crate::common::types::block_origin::BlockOrigin;
+use crate::common::types::blockchain_error::BlockchainError;
 use crate::common::types::light_authority_set::LightAuthoritySet;
 use crate::common::types::next_change_in_authority::NextChangeInAuthority;
 use crate::common::utils::{
-    delete_next_authority_change, fetch_light_authority_set, fetch_next_authority_change,
-    insert_light_authority_set, GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY,
+    delete_next_authority_change, delete_next_forced_authority_change, fetch_light_authority_set,
+    fetch_next_authority_change, fetch_next_forced_authority_change, insert_light_authority_set,
+    GRANDPA_AUTHORITY_CHANGE_INTERMEDIATE_KEY, GRANDPA_FORCED_AUTHORITY_CHANGE_INTERMEDIATE_KEY,
 };
 use parity_scale_codec::alloc::borrow::Cow;
 use parity_scale_codec::alloc::sync::Arc;
 use sp_finality_grandpa::{ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
-use sp_runtime::generic::OpaqueDigestItemId;
+use sp_runtime::generic::{BlockId, OpaqueDigestItemId};
 use sp_runtime::traits::Header;
-use sp_runtime::traits::{Block as BlockT, NumberFor};
+use sp_runtime::traits::{Block as BlockT, CheckedAdd, NumberFor, Zero};
 
-fn find_scheduled_change<B: BlockT>(header: &B::Header) -> Option<ScheduledChange<NumberFor<B>>> {
+pub(crate) fn find_scheduled_change<B: BlockT>(
+    header: &B::Header,
+) -> Option<ScheduledChange<NumberFor<B>>> {
     let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
 
     let filter_log = |log: ConsensusLog<NumberFor<B>>| match log {
@@ -31,6 +35,81 @@ fn find_scheduled_change<B: BlockT>(header: &B::Header) -> Option<ScheduledChang
         .convert_first(|l| l.try_to(id).and_then(filter_log))
 }
 
+pub(crate) fn find_forced_change<B: BlockT>(
+    header: &B::Header,
+) -> Option<(NumberFor<B>, ScheduledChange<NumberFor<B>>)> {
+    let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
+
+    let filter_log = |log: ConsensusLog<NumberFor<B>>| match log {
+        ConsensusLog::ForcedChange(median_last_finalized, change) => {
+            Some((median_last_finalized, change))
+        }
+        _ => None,
+    };
+
+    // find the first consensus digest with the right ID which converts to
+    // the right kind of consensus log.
+    header
+        .digest()
+        .convert_first(|l| l.try_to(id).and_then(filter_log))
+}
+
+/// Takes pending change out of the aux storage if it is due to be enacted
+/// at given header. Second element tells if the change remains pending.
+fn take_due_change<S, Block, F>(
+    storage: Arc<S>,
+    header: &Block::Header,
+    possible_change: Option<NextChangeInAuthority<Block>>,
+    delete_change: F,
+) -> Result<(Option<NextChangeInAuthority<Block>>, bool), String>
+where
+    S: AuxStore,
+    Block: BlockT,
+    F: Fn(Arc<S>) -> Result<(), BlockchainError>,
+{
+    match possible_change {
+        Some(authority_change) => {
+            if authority_change.next_change_at == *header.number() {
+                delete_change(storage).map_err(|e| format!("{}", e))?;
+                Ok((Some(authority_change), false))
+            } else {
+                Ok((None, true))
+            }
+        }
+        None => Ok((None, false)),
+    }
+}
+
+/// Drops pending change if header signalling it isn't finalized, as such headers
+/// are discarded once a header skips ahead of them. Change which is due at one of
+/// the skipped headers can't be enacted, so the header enacting it need to be
+/// ingested before skipping ahead.
+fn retain_change_on_skip<S, Block, F>(
+    storage: Arc<S>,
+    header: &Block::Header,
+    last_finalized_number: NumberFor<Block>,
+    possible_change: Option<NextChangeInAuthority<Block>>,
+    delete_change: F,
+) -> Result<Option<NextChangeInAuthority<Block>>, String>
+where
+    S: AuxStore,
+    Block: BlockT,
+    F: Fn(Arc<S>) -> Result<(), BlockchainError>,
+{
+    match possible_change {
+        Some(authority_change)
+            if authority_change.block_enacting_this_change.1 > last_finalized_number =>
+        {
+            delete_change(storage).map_err(|e| format!("{}", e))?;
+            Ok(None)
+        }
+        Some(authority_change) if authority_change.next_change_at < *header.number() => {
+            Err("Header enacting pending authority set change need to be ingested first.".into())
+        }
+        possible_change => Ok(possible_change),
+    }
+}
+
 pub struct GrandpaVerifier<S> {
     storage: Arc<S>,
 }
@@ -55,23 +134,83 @@ where
         justification: Option<Vec<u8>>,
         _body: Option<Vec<<Block as BlockT>::Extrinsic>>,
     ) -> Result<BlockImportParams<Block>, String> {
-        let (possible_authority_change, scheduled_change_exists) = {
-            let possible_authority_change =
-                fetch_next_authority_change::<S, Block>(self.storage.clone())
-                    .map_err(|e| format!("{}", e))?;
-            match possible_authority_change {
-                Some(authority_change) => {
-                    if authority_change.next_change_at == *header.number() {
+        let possible_scheduled_change =
+            fetch_next_authority_change::<S, Block>(self.storage.clone())
+                .map_err(|e| format!("{}", e))?;
+        let possible_forced_change =
+            fetch_next_forced_authority_change::<S, Block>(self.storage.clone())
+                .map_err(|e| format!("{}", e))?;
+
+        // Parent is unknown only if header is allowed to skip ahead of the best header
+        let possible_parent_header = self
+            .storage
+            .header(BlockId::<Block>::Hash(*header.parent_hash()))
+            .map_err(|e| format!("{}", e))?;
+        let (possible_scheduled_change, possible_forced_change) =
+            if possible_parent_header.is_none() {
+                let info = self.storage.info();
+                // Initial header is trusted, so it is treated as finalized
+                let last_finalized_number = if info.finalized_hash == Default::default() {
+                    self.storage
+                        .number(info.genesis_hash)
+                        .map_err(|e| format!("{}", e))?
+                        .unwrap_or_else(Zero::zero)
+                } else {
+                    info.finalized_number
+                };
+                (
+                    retain_change_on_skip(
+                        self.storage.clone(),
+                        &header,
+                        last_finalized_number,
+                        possible_scheduled_change,
+                        delete_next_authority_change,
+                    )?,
+                    retain_change_on_skip(
+                        self.storage.clone(),
+                        &header,
+                        last_finalized_number,
+                        possible_forced_change,
+                        delete_next_forced_authority_change,
+                    )?,
+                )
+            } else {
+                (possible_scheduled_change, possible_forced_change)
+            };
+
+        let (possible_forced_authority_change, forced_change_exists) = take_due_change(
+            self.storage.clone(),
+            &header,
+            possible_forced_change,
+            delete_next_forced_authority_change,
+        )?;
+
+        let (possible_authority_change, scheduled_change_exists) =
+            match possible_forced_authority_change.as_ref() {
+                // A forced change replaces the authority set signalling it, so any
+                // pending standard change is discarded, unless forced change
+                // depends on it being enacted first.
+                Some(forced_change) => {
+                    if let Some(scheduled_change) = possible_scheduled_change.as_ref() {
+                        if Some(scheduled_change.next_change_at)
+                            <= forced_change.median_last_finalized
+                        {
+                            return Err(
+                                "Forced change depends on unapplied scheduled change.".into()
+                            );
+                        }
                         delete_next_authority_change(self.storage.clone())
                             .map_err(|e| format!("{}", e))?;
-                        (Some(authority_change), false)
-                    } else {
-                        (None, true)
                     }
+                    (None, false)
                 }
-                None => (None, false),
-            }
-        };
+                None => take_due_change(
+                    self.storage.clone(),
+                    &header,
+                    possible_scheduled_change,
+                    delete_next_authority_change,
+                )?,
+            };
 
         if let Some(authority_change) = possible_authority_change.as_ref() {
             let (_, enacting_header_number) = authority_change.block_enacting_this_change;
@@ -88,8 +227,13 @@ where
                     if scheduled_change_exists {
                         Err("Scheduled change already exists.")
                     } else {
+                        let next_change_at =
+                            header
+                                .number()
+                                .checked_add(&scheduled_change.delay)
+                                .ok_or("Scheduled change delay overflows block number")?;
                         Ok(Some(NextChangeInAuthority::new(
-                            *header.number() + scheduled_change.delay,
+                            next_change_at,
                             (header.hash(), *header.number()),
                             scheduled_change,
                         )))
@@ -98,6 +242,28 @@ where
                 None => Ok(None),
             }?;
 
+        let found_forced_authority_change = find_forced_change::<Block>(&header);
+        let possible_next_forced_authority_change: Option<NextChangeInAuthority<Block>> =
+            match found_forced_authority_change {
+                Some((median_last_finalized, forced_change)) => {
+                    if forced_change_exists {
+                        Err("Forced change already exists.")
+                    } else {
+                        let next_change_at = header
+                            .number()
+                            .checked_add(&forced_change.delay)
+                            .ok_or("Forced change delay overflows block number")?;
+                        Ok(Some(NextChangeInAuthority::new_forced(
+                            next_change_at,
+                            (header.hash(), *header.number()),
+                            forced_change,
+                            median_last_finalized,
+                        )))
+                    }
+                }
+                None => Ok(None),
+            }?;
+
         let mut block_import_params: BlockImportParams<Block> =
             BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
         block_import_params.justification = justification;
@@ -107,8 +273,18 @@ where
                 Box::new(next_authority_change),
             );
         }
+        if let Some(next_forced_authority_change) = possible_next_forced_authority_change {
+            block_import_params.intermediates.insert(
+                Cow::from(GRANDPA_FORCED_AUTHORITY_CHANGE_INTERMEDIATE_KEY),
+                Box::new(next_forced_authority_change),
+            );
+        }
 
-        if let Some(authority_change) = possible_authority_change {
+        // Forced change doesn't wait for finality of the block signalling it,
+        // it is enacted as soon as block at `next_change_at` is imported.
+        if let Some(authority_change) =
+            possible_authority_change.or(possible_forced_authority_change)
+        {
             let possible_current_authority_set =
                 fetch_light_authority_set(self.storage.clone()).map_err(|e| format!("{}", e))?;
             let current_authority_set = if possible_current_authority_set.is_none() {
@@ -119,7 +295,8 @@ where
             let next_authority_set = LightAuthoritySet::construct_next_authority_set(
                 &current_authority_set,
                 authority_change.change.next_authorities,
-            );
+            )
+            .ok_or("Authority set id overflows")?;
             insert_light_authority_set(self.storage.clone(), next_authority_set)
                 .map_err(|e| format!("{}", e))?;
         }
@@ -0,0 +1,101 @@
+use crate::common::types::blockchain_error::BlockchainError;
+use crate::common::types::blockchain_result::BlockchainResult;
+use crate::common::types::light_authority_set::LightAuthoritySet;
+use crate::justification::{GrandpaJustification, ProvableJustification};
+use crate::verifier::find_scheduled_change;
+use finality_grandpa::BlockNumberOps;
+use parity_scale_codec::{Decode, Encode};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero};
+
+/// Mirrors `WarpSyncFragment` of `sc-finality-grandpa-warp-sync`. Header is the last one
+/// finalized by its authority set, and it schedules the authority set following it.
+#[derive(Encode, Decode)]
+pub struct WarpSyncFragment<Block: BlockT> {
+    pub header: Block::Header,
+    pub justification: GrandpaJustification<Block>,
+}
+
+/// Mirrors `WarpSyncProof` of `sc-finality-grandpa-warp-sync`. Fragments are ordered by
+/// authority set finalizing them, and `is_finished` tells if header of the last fragment is
+/// the latest finalized one, in which case it doesn't need to schedule an authority set change.
+#[derive(Encode, Decode)]
+pub struct WarpSyncProof<Block: BlockT> {
+    pub proofs: Vec<WarpSyncFragment<Block>>,
+    pub is_finished: bool,
+}
+
+impl<Block: BlockT> WarpSyncProof<Block>
+where
+    NumberFor<Block>: BlockNumberOps,
+{
+    /// Verifies fragments one after another, each against the authority set scheduled by the
+    /// previous one, starting with the given authority set. Gives the last fragment, the
+    /// authority set finalizing its header and the one in effect after it.
+    pub fn verify(
+        &self,
+        authority_set: &LightAuthoritySet,
+    ) -> BlockchainResult<(
+        &WarpSyncFragment<Block>,
+        LightAuthoritySet,
+        LightAuthoritySet,
+    )> {
+        let mut authority_set = authority_set.clone();
+        let mut possible_previous_number: Option<NumberFor<Block>> = None;
+        for (index, fragment) in self.proofs.iter().enumerate() {
+            let hash = fragment.header.hash();
+            let number = *fragment.header.number();
+            if let Some(previous_number) = possible_previous_number {
+                if number <= previous_number {
+                    return Err(BlockchainError::Msg(format!(
+                        "Fragment header {} does not follow fragment header {}",
+                        number, previous_number
+                    )));
+                }
+            }
+            possible_previous_number = Some(number);
+
+            ProvableJustification::<Block>::verify_finalization(
+                &fragment.justification,
+                authority_set.set_id(),
+                (hash, number),
+                &authority_set.authorities(),
+            )
+            .map_err(|e| BlockchainError::Msg(format!("Fragment header {}: {}", number, e)))?;
+
+            let is_last_fragment = index + 1 == self.proofs.len();
+            match find_scheduled_change::<Block>(&fragment.header) {
+                Some(scheduled_change) => {
+                    // Change enacted later would be finalized by headers which aren't part of the proof
+                    if !scheduled_change.delay.is_zero() {
+                        return Err(BlockchainError::Msg(format!(
+                            "Authority set change scheduled by fragment header {} need to be enacted at it",
+                            number
+                        )));
+                    }
+                    let next_authority_set = LightAuthoritySet::construct_next_authority_set(
+                        &authority_set,
+                        scheduled_change.next_authorities,
+                    )
+                    .ok_or_else(|| BlockchainError::Msg("Authority set id overflows".into()))?;
+                    if is_last_fragment {
+                        return Ok((fragment, authority_set, next_authority_set));
+                    }
+                    authority_set = next_authority_set;
+                }
+                None if is_last_fragment && self.is_finished => {
+                    return Ok((fragment, authority_set.clone(), authority_set));
+                }
+                None => {
+                    return Err(BlockchainError::Msg(format!(
+                        "Fragment header {} is missing authority set change",
+                        number
+                    )))
+                }
+            }
+        }
+
+        Err(BlockchainError::Msg(
+            "Warp sync proof need to have at least one fragment".into(),
+        ))
+    }
+}